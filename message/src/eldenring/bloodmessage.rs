@@ -11,6 +11,50 @@ pub struct RequestCreateBloodMessageParams {
     pub group_passwords: Vec<String>,
 }
 
+/// Decoded contents of [RequestCreateBloodMessageParams::data]. Besides the message itself this
+/// contains the appearance of the author, which is used to display the phantom performing the
+/// gesture when the message is read. The layout was derived from captured traffic, fields
+/// prefixed unk are not understood yet.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BloodMessageData {
+    pub unk0: u32,
+    /// Size of the remainder of the message data.
+    pub size: u32,
+    pub equipment: BloodMessageEquipment,
+    pub unk1: [i32; 12],
+    /// FACE chunk describing the author's character appearance.
+    pub face: [[u8; 32]; 9],
+    pub unk2: [u8; 32],
+    pub unk3: [u8; 20],
+    /// Where the message was written.
+    pub location: Location,
+    pub angle: f32,
+    /// Template of the first sentence, the ones with a **** placeholder for the word.
+    pub template1: u16,
+    /// Conjunction joining both sentences. -1 if the message consists of a single sentence.
+    pub conjunction: i8,
+    pub unk4: u8,
+    pub word1: i32,
+    pub template2: u16,
+    pub unk5: u16,
+    pub word2: i32,
+    /// Gesture the phantom performs when the message is read.
+    pub gesture: u16,
+    pub unk6: [u8; 14],
+    pub play_region: u32,
+    pub unk7: u32,
+}
+
+/// Equipment of the author at the time of writing. Empty slots are -1.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BloodMessageEquipment {
+    /// Alternating left and right hand armaments.
+    pub weapons: [i32; 6],
+    pub ammunition: [i32; 6],
+    /// Head, chest, arms and legs.
+    pub protectors: [i32; 4],
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ResponseCreateBloodMessageParams {
     pub identifier: ObjectIdentifier,
@@ -69,9 +113,10 @@ pub struct ResponseRemoveBloodMessageParams {}
 
 #[cfg(test)]
 mod test {
+    use super::BloodMessageData;
     use super::RequestCreateBloodMessageParams;
     use super::RequestGetBloodMessageListParams;
-    use wire::deserialize;
+    use wire::{deserialize, serialize};
 
     #[test]
     fn deserialize_create_bloodmessage() {
@@ -89,6 +134,46 @@ mod test {
         assert_eq!(deserialized.unk, 0);
     }
 
+    #[test]
+    fn deserialize_bloodmessage_data() {
+        let deserialized: BloodMessageData =
+            deserialize(include_bytes!("../../test/data/BloodMessageData.bin")).unwrap();
+
+        assert_eq!(deserialized.size, 512);
+        assert_eq!(deserialized.equipment.weapons[0], 24000000);
+        assert_eq!(
+            deserialized.equipment.protectors,
+            [10000, 10100, 10200, 10300]
+        );
+        assert_eq!(&deserialized.face[0][0..4], b"FACE");
+        assert_eq!(deserialized.location.map, 14000);
+        assert_eq!(deserialized.template1, 10000);
+        assert_eq!(deserialized.conjunction, -1);
+        assert_eq!(deserialized.word1, 34004);
+        assert_eq!(deserialized.template2, 0);
+        assert_eq!(deserialized.word2, 0);
+        assert_eq!(deserialized.play_region, 1400001);
+    }
+
+    #[test]
+    fn bloodmessage_data_round_trips() {
+        let data = include_bytes!("../../test/data/BloodMessageData.bin");
+        let deserialized: BloodMessageData = deserialize(data).unwrap();
+
+        assert_eq!(serialize(&deserialized).unwrap(), data);
+    }
+
+    #[test]
+    fn deserialize_create_bloodmessage_data() {
+        let request: RequestCreateBloodMessageParams = deserialize(include_bytes!(
+            "../../test/data/RequestCreateBloodMessage.bin"
+        ))
+        .unwrap();
+        let deserialized: BloodMessageData = deserialize(&request.data).unwrap();
+
+        assert_eq!(deserialized.play_region, request.area.play_region);
+    }
+
     #[test]
     fn deserialize_get_bloodmessage_list() {
         let deserialized: RequestGetBloodMessageListParams = deserialize(include_bytes!(
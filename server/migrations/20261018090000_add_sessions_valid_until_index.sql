@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS idx_sessions_valid_until ON sessions (valid_until);
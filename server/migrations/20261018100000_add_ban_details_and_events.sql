@@ -0,0 +1,17 @@
+ALTER TABLE bans ADD COLUMN expires_at BIGINT;
+ALTER TABLE bans ADD COLUMN reason TEXT;
+ALTER TABLE bans ADD COLUMN moderator VARCHAR;
+
+CREATE INDEX IF NOT EXISTS idx_bans_expires_at ON bans (expires_at);
+
+CREATE TABLE ban_events (
+    ban_event_id BIGSERIAL PRIMARY KEY,
+    external_id VARCHAR NOT NULL,
+    action VARCHAR NOT NULL,
+    moderator VARCHAR,
+    reason TEXT,
+    expires_at BIGINT,
+    created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())
+);
+
+CREATE INDEX IF NOT EXISTS idx_ban_events_external_id ON ban_events (external_id);
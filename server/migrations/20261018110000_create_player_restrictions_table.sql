@@ -0,0 +1,13 @@
+CREATE TABLE player_restrictions (
+    external_id VARCHAR NOT NULL,
+    restriction VARCHAR NOT NULL,
+    reason TEXT,
+    moderator VARCHAR,
+    expires_at BIGINT,
+    created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW()),
+    PRIMARY KEY (external_id, restriction)
+);
+
+ALTER TABLE bloodmessages ADD COLUMN shadowed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE bloodstains ADD COLUMN shadowed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE ghostdata ADD COLUMN shadowed BOOLEAN NOT NULL DEFAULT FALSE;
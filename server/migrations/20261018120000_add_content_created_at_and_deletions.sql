@@ -0,0 +1,17 @@
+ALTER TABLE bloodmessages ADD COLUMN created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW());
+ALTER TABLE bloodstains ADD COLUMN created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW());
+ALTER TABLE ghostdata ADD COLUMN created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW());
+
+CREATE INDEX IF NOT EXISTS idx_bloodmessages_player_id ON bloodmessages (player_id);
+CREATE INDEX IF NOT EXISTS idx_bloodstains_player_id ON bloodstains (player_id);
+CREATE INDEX IF NOT EXISTS idx_ghostdata_player_id ON ghostdata (player_id);
+
+CREATE TABLE content_deletions (
+    content_deletion_id BIGSERIAL PRIMARY KEY,
+    content_type VARCHAR NOT NULL,
+    content_id BIGINT NOT NULL,
+    player_id INTEGER NOT NULL,
+    moderator VARCHAR,
+    reason TEXT,
+    created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())
+);
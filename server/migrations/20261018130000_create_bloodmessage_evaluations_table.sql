@@ -0,0 +1,8 @@
+CREATE TABLE bloodmessage_evaluations (
+    bloodmessage_id BIGINT NOT NULL REFERENCES bloodmessages (bloodmessage_id) ON DELETE CASCADE,
+    player_id INTEGER NOT NULL,
+    rating INTEGER NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW()),
+    updated_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW()),
+    PRIMARY KEY (bloodmessage_id, player_id)
+);
@@ -0,0 +1,3 @@
+CREATE INDEX IF NOT EXISTS idx_bloodmessages_group_passwords ON bloodmessages USING GIN (group_passwords);
+CREATE INDEX IF NOT EXISTS idx_bloodstains_group_passwords ON bloodstains USING GIN (group_passwords);
+CREATE INDEX IF NOT EXISTS idx_ghostdata_group_passwords ON ghostdata USING GIN (group_passwords);
@@ -0,0 +1,8 @@
+ALTER TABLE player_equipments ADD COLUMN created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW());
+
+CREATE INDEX IF NOT EXISTS idx_player_equipments_player_id ON player_equipments (player_id);
+
+CREATE INDEX IF NOT EXISTS idx_bloodmessages_created_at ON bloodmessages (created_at);
+CREATE INDEX IF NOT EXISTS idx_bloodstains_created_at ON bloodstains (created_at);
+CREATE INDEX IF NOT EXISTS idx_ghostdata_created_at ON ghostdata (created_at);
+CREATE INDEX IF NOT EXISTS idx_player_equipments_created_at ON player_equipments (created_at);
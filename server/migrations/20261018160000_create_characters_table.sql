@@ -0,0 +1,22 @@
+CREATE TABLE characters (
+    character_id SERIAL PRIMARY KEY,
+    player_id INTEGER NOT NULL,
+    -- Unknown until the first status update of the character came in.
+    name VARCHAR,
+    created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW()),
+    last_seen_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())
+);
+
+CREATE INDEX idx_characters_player_id ON characters (player_id, last_seen_at DESC);
+CREATE INDEX idx_characters_player_id_name ON characters (player_id, name);
+
+-- Blood messages keep the character ID the client sent along, it's handed back to clients
+-- listing the messages. It doesn't refer to any of the characters above.
+ALTER TABLE bloodmessages RENAME COLUMN character_id TO client_character_id;
+ALTER TABLE bloodmessages ADD COLUMN character_id INTEGER
+    REFERENCES characters (character_id) ON DELETE SET NULL;
+
+ALTER TABLE bloodstains ADD COLUMN character_id INTEGER
+    REFERENCES characters (character_id) ON DELETE SET NULL;
+ALTER TABLE ghostdata ADD COLUMN character_id INTEGER
+    REFERENCES characters (character_id) ON DELETE SET NULL;
@@ -0,0 +1,38 @@
+CREATE TABLE character_snapshots (
+    character_snapshot_id BIGSERIAL PRIMARY KEY,
+    character_id INTEGER NOT NULL REFERENCES characters (character_id) ON DELETE CASCADE,
+    player_id INTEGER NOT NULL,
+    play_region INTEGER NOT NULL,
+    level INTEGER NOT NULL,
+    vigor INTEGER NOT NULL,
+    mind INTEGER NOT NULL,
+    endurance INTEGER NOT NULL,
+    vitality INTEGER NOT NULL,
+    strength INTEGER NOT NULL,
+    dexterity INTEGER NOT NULL,
+    intelligence INTEGER NOT NULL,
+    faith INTEGER NOT NULL,
+    arcane INTEGER NOT NULL,
+    runes_owned BIGINT NOT NULL,
+    max_reinforce_level INTEGER NOT NULL,
+    weapons_left_hand INTEGER[] NOT NULL,
+    weapons_right_hand INTEGER[] NOT NULL,
+    -- Head, chest, arms and legs.
+    protectors INTEGER[] NOT NULL,
+    accessories INTEGER[] NOT NULL,
+    spells INTEGER[] NOT NULL,
+    game_clear_count INTEGER NOT NULL,
+    death_count INTEGER NOT NULL,
+    total_summon_count INTEGER NOT NULL,
+    coop_success_count INTEGER NOT NULL,
+    invaders_killed_count INTEGER NOT NULL,
+    hosts_killed_count INTEGER NOT NULL,
+    play_time INTEGER NOT NULL,
+    owned_dlcs INTEGER[] NOT NULL,
+    visited_areas INTEGER[] NOT NULL,
+    regulation_version BIGINT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())
+);
+
+CREATE INDEX idx_character_snapshots_character_id ON character_snapshots (character_id, created_at DESC);
+CREATE INDEX idx_character_snapshots_player_id ON character_snapshots (player_id);
@@ -0,0 +1,12 @@
+CREATE TABLE cheat_flags (
+    cheat_flag_id BIGSERIAL PRIMARY KEY,
+    external_id VARCHAR NOT NULL,
+    player_id INTEGER NOT NULL,
+    character_id INTEGER REFERENCES characters (character_id) ON DELETE SET NULL,
+    rule VARCHAR NOT NULL,
+    evidence TEXT NOT NULL,
+    action VARCHAR NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())
+);
+
+CREATE INDEX idx_cheat_flags_external_id ON cheat_flags (external_id);
@@ -0,0 +1,15 @@
+-- Ratings received across all blood messages of a player, kept up to date on evaluation so
+-- weighted selection doesn't have to add them up per request.
+ALTER TABLE players
+    ADD COLUMN bloodmessage_rating_good INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN bloodmessage_rating_bad INTEGER NOT NULL DEFAULT 0;
+
+UPDATE players SET
+    bloodmessage_rating_good = totals.good,
+    bloodmessage_rating_bad = totals.bad
+FROM (
+    SELECT player_id, SUM(rating_good) AS good, SUM(rating_bad) AS bad
+    FROM bloodmessages
+    GROUP BY player_id
+) totals
+WHERE players.player_id = totals.player_id;
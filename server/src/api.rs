@@ -2,14 +2,23 @@ use std::sync::Arc;
 
 use sqlx::{Pool, Postgres};
 
-use crate::services::eldenring::GameServices;
+use crate::{services::eldenring::GameServices, shutdown::ShutdownCoordinator};
 
 pub mod auth;
 pub mod ban;
+pub mod character;
+pub mod cheat;
+pub mod client_version;
+pub mod content;
 pub mod health;
 pub mod notification;
+pub mod rate_limit;
+pub mod restriction;
+pub mod retention;
+pub mod shutdown;
 
 pub struct AppState {
     pub database: Pool<Postgres>,
     pub services: Arc<GameServices>,
+    pub shutdown: Arc<ShutdownCoordinator>,
 }
@@ -1,20 +1,52 @@
 use std::{
+    collections::HashMap,
     future::{ready, Ready},
     pin::Pin,
+    rc::Rc,
+    str::FromStr,
     task::{Context, Poll},
 };
 
 use actix_web::{
     dev::{Service, ServiceRequest, ServiceResponse, Transform},
     error::ErrorUnauthorized,
-    http::header::HeaderValue,
+    HttpMessage,
 };
 
-pub struct CheckKey(String);
+/// Label of the API key a request was authorized with. Used to attribute moderation actions.
+#[derive(Clone, Debug)]
+pub struct ApiKeyLabel(pub String);
+
+/// Label given to the key passed through `--api-key`.
+pub const DEFAULT_API_KEY_LABEL: &str = "default";
+
+/// API key with a label identifying who holds it. Parsed from `label=key`.
+#[derive(Clone, Debug)]
+pub struct LabeledApiKey {
+    pub label: String,
+    pub key: String,
+}
+
+impl FromStr for LabeledApiKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((label, key)) if !label.is_empty() && !key.is_empty() => Ok(Self {
+                label: label.to_string(),
+                key: key.to_string(),
+            }),
+            _ => Err("API key must be formatted as label=key".to_string()),
+        }
+    }
+}
+
+/// Checks requests against a set of API keys, keyed by the key with its label as value.
+pub struct CheckKey(Rc<HashMap<String, String>>);
 
 impl CheckKey {
-    pub fn new<S: ToString>(key: S) -> Self {
-        Self(key.to_string())
+    pub fn new(keys: HashMap<String, String>) -> Self {
+        Self(Rc::new(keys))
     }
 }
 
@@ -30,13 +62,13 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        let api_key = self.0.clone();
-        ready(Ok(CheckKeyMiddleware { service, api_key }))
+        let api_keys = self.0.clone();
+        ready(Ok(CheckKeyMiddleware { service, api_keys }))
     }
 }
 
 pub struct CheckKeyMiddleware<S> {
-    api_key: String,
+    api_keys: Rc<HashMap<String, String>>,
     service: S,
 }
 
@@ -54,12 +86,17 @@ where
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let expected = HeaderValue::from_str(&self.api_key).unwrap();
-        let authorized = req
+        let label = req
             .headers()
             .get("X-Auth-Token")
-            .map(|v| v == expected)
-            .unwrap_or_default();
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| self.api_keys.get(v))
+            .cloned();
+
+        let authorized = label.is_some();
+        if let Some(label) = label {
+            req.extensions_mut().insert(ApiKeyLabel(label));
+        }
 
         let fut = self.service.call(req);
         Box::pin(async move {
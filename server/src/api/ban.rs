@@ -1,13 +1,19 @@
-use std::error::Error;
+use std::{
+    error::Error,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use actix_web::{
     delete, get, post,
-    web::{Data, Json, Path, Query},
-    Responder,
+    web::{Data, Json, Path, Query, ReqData},
+    HttpResponse, Responder,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::api::AppState;
+use crate::{
+    api::{auth::ApiKeyLabel, AppState},
+    bans::BanStatus,
+};
 
 const DEFAULT_INDEX_LIMIT: i32 = 100;
 
@@ -15,12 +21,15 @@ const DEFAULT_INDEX_LIMIT: i32 = 100;
 async fn get_ban(
     state: Data<AppState>,
     Query(pagination): Query<PaginationParameters>,
+    Query(filter): Query<BanFilterParameters>,
 ) -> Result<impl Responder, Box<dyn Error>> {
-    let total = state.services.bans.get_total().await?;
+    let status = filter.status.unwrap_or_default();
+    let total = state.services.bans.get_total(status).await?;
     let entries = state
         .services
         .bans
         .list_bans(
+            status,
             pagination.limit.unwrap_or(DEFAULT_INDEX_LIMIT) as i64,
             pagination.offset.unwrap_or(0) as i64,
         )
@@ -38,28 +47,73 @@ async fn get_ban_by_id(
     let ban = state.services.bans.get_ban(external_id).await?;
 
     match ban {
-        Some(ban) => Ok(actix_web::HttpResponse::Ok().json(ban)),
-        None => Ok(actix_web::HttpResponse::NotFound().finish()),
+        Some(ban) => Ok(HttpResponse::Ok().json(ban)),
+        None => Ok(HttpResponse::NotFound().finish()),
     }
 }
 
+#[get("/ban/{external_id}/events")]
+async fn get_ban_events(
+    state: Data<AppState>,
+    external_id: Path<(String,)>,
+    Query(pagination): Query<PaginationParameters>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let external_id = &external_id.into_inner().0;
+    let total = state.services.bans.get_events_total(external_id).await?;
+    let entries = state
+        .services
+        .bans
+        .list_events(
+            external_id,
+            pagination.limit.unwrap_or(DEFAULT_INDEX_LIMIT) as i64,
+            pagination.offset.unwrap_or(0) as i64,
+        )
+        .await?;
+
+    Ok(Json(PaginatedResponse::new(total, entries)))
+}
+
 #[post("/ban")]
 async fn post_ban(
     state: Data<AppState>,
+    moderator: ReqData<ApiKeyLabel>,
     request: Json<NewBan>,
 ) -> Result<impl Responder, Box<dyn Error>> {
-    let ban_id = state.services.bans.add_ban(&request.external_id).await?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    if request
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+    {
+        return Ok(HttpResponse::BadRequest().body("expires_at lies in the past"));
+    }
+
+    let ban_id = state
+        .services
+        .bans
+        .add_ban(
+            &request.external_id,
+            request.reason.as_deref(),
+            request.expires_at,
+            &moderator.0,
+        )
+        .await?;
 
-    Ok(Json(ban_id))
+    Ok(HttpResponse::Ok().json(ban_id))
 }
 
 #[delete("/ban/{external_id}")]
 async fn delete_ban(
     state: Data<AppState>,
+    moderator: ReqData<ApiKeyLabel>,
     external_id: Path<(String,)>,
+    Query(params): Query<DeleteBanParameters>,
 ) -> Result<impl Responder, Box<dyn Error>> {
     let external_id = &external_id.into_inner().0;
-    let deleted = state.services.bans.delete_ban(external_id).await?;
+    let deleted = state
+        .services
+        .bans
+        .delete_ban(external_id, params.reason.as_deref(), &moderator.0)
+        .await?;
 
     Ok(Json(deleted))
 }
@@ -67,6 +121,20 @@ async fn delete_ban(
 #[derive(Debug, Deserialize)]
 struct NewBan {
     external_id: String,
+    /// Free-text reason shown to other moderators.
+    reason: Option<String>,
+    /// Unix timestamp at which the ban lapses. Omit for a permanent ban.
+    expires_at: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteBanParameters {
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BanFilterParameters {
+    status: Option<BanStatus>,
 }
 
 #[derive(Deserialize)]
@@ -0,0 +1,96 @@
+use std::error::Error;
+
+use actix_web::{
+    get,
+    web::{Data, Json, Path, Query},
+    HttpResponse, Responder,
+};
+use serde::Serialize;
+
+use crate::{
+    api::{
+        ban::{PaginatedResponse, PaginationParameters},
+        AppState,
+    },
+    characters::{CharacterRecord, CharacterSnapshotRecord},
+    protocol::player_external_id,
+};
+
+const DEFAULT_INDEX_LIMIT: i32 = 100;
+
+/// Characters a player has logged in with, most recently seen first.
+#[get("/player/{external_id}/characters")]
+async fn get_player_characters(
+    state: Data<AppState>,
+    external_id: Path<(u64,)>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let external_id = player_external_id(external_id.into_inner().0);
+    let characters = state
+        .services
+        .characters
+        .list_characters(&external_id)
+        .await?;
+
+    Ok(Json(characters))
+}
+
+#[derive(Serialize)]
+struct CharacterResponse {
+    #[serde(flatten)]
+    character: CharacterRecord,
+    /// Most recent status of the character. Absent if none was stored yet.
+    snapshot: Option<CharacterSnapshotRecord>,
+}
+
+/// The character with its current build and stats.
+#[get("/character/{character_id}")]
+async fn get_character(
+    state: Data<AppState>,
+    character_id: Path<(i32,)>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let character_id = character_id.into_inner().0;
+    let Some(character) = state
+        .services
+        .characters
+        .get_character(character_id)
+        .await?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let snapshot = state
+        .services
+        .characters
+        .latest_snapshot(character_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(CharacterResponse {
+        character,
+        snapshot,
+    }))
+}
+
+/// Stored statuses of the character, newest first.
+#[get("/character/{character_id}/snapshots")]
+async fn get_character_snapshots(
+    state: Data<AppState>,
+    character_id: Path<(i32,)>,
+    Query(pagination): Query<PaginationParameters>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let character_id = character_id.into_inner().0;
+    let total = state
+        .services
+        .characters
+        .get_snapshots_total(character_id)
+        .await?;
+    let entries = state
+        .services
+        .characters
+        .list_snapshots(
+            character_id,
+            pagination.limit.unwrap_or(DEFAULT_INDEX_LIMIT) as i64,
+            pagination.offset.unwrap_or(0) as i64,
+        )
+        .await?;
+
+    Ok(Json(PaginatedResponse::new(total, entries)))
+}
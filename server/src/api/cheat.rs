@@ -0,0 +1,60 @@
+use std::error::Error;
+
+use actix_web::{
+    get,
+    web::{Data, Json, Path, Query},
+    Responder,
+};
+
+use crate::api::{
+    ban::{PaginatedResponse, PaginationParameters},
+    AppState,
+};
+
+const DEFAULT_INDEX_LIMIT: i32 = 100;
+
+/// Cheat rules broken by players, most recent first.
+#[get("/cheatflag")]
+async fn get_cheat_flags(
+    state: Data<AppState>,
+    Query(pagination): Query<PaginationParameters>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let total = state.services.cheats.get_flags_total(None).await?;
+    let entries = state
+        .services
+        .cheats
+        .list_flags(
+            None,
+            pagination.limit.unwrap_or(DEFAULT_INDEX_LIMIT) as i64,
+            pagination.offset.unwrap_or(0) as i64,
+        )
+        .await?;
+
+    Ok(Json(PaginatedResponse::new(total, entries)))
+}
+
+/// Cheat rules broken by the player, most recent first.
+#[get("/cheatflag/{external_id}")]
+async fn get_cheat_flags_by_id(
+    state: Data<AppState>,
+    external_id: Path<(String,)>,
+    Query(pagination): Query<PaginationParameters>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let external_id = external_id.into_inner().0;
+    let total = state
+        .services
+        .cheats
+        .get_flags_total(Some(&external_id))
+        .await?;
+    let entries = state
+        .services
+        .cheats
+        .list_flags(
+            Some(&external_id),
+            pagination.limit.unwrap_or(DEFAULT_INDEX_LIMIT) as i64,
+            pagination.offset.unwrap_or(0) as i64,
+        )
+        .await?;
+
+    Ok(Json(PaginatedResponse::new(total, entries)))
+}
@@ -0,0 +1,22 @@
+use actix_web::{get, web::Data, web::Json, Responder};
+use serde::Serialize;
+
+use crate::{api::AppState, client_version::ClientVersionCount};
+
+#[derive(Serialize)]
+struct ClientVersionDistribution {
+    /// Connected clients across all versions.
+    total: usize,
+    versions: Vec<ClientVersionCount>,
+}
+
+/// Versions of the Waygate clients currently connected, newest first.
+#[get("/clientversions")]
+async fn get_client_versions(state: Data<AppState>) -> impl Responder {
+    let versions = state.services.client_versions.distribution();
+
+    Json(ClientVersionDistribution {
+        total: versions.iter().map(|v| v.connections).sum(),
+        versions,
+    })
+}
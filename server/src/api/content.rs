@@ -0,0 +1,131 @@
+use std::{collections::HashMap, error::Error};
+
+use actix_web::{
+    delete, get,
+    web::{Data, Json, Path, Query, ReqData},
+    HttpResponse, Responder,
+};
+use serde::Deserialize;
+
+use crate::{
+    api::{
+        auth::ApiKeyLabel,
+        ban::{PaginatedResponse, PaginationParameters},
+        AppState,
+    },
+    content::{ContentFilter, ContentKind},
+    protocol::player_external_id,
+};
+
+const DEFAULT_INDEX_LIMIT: i32 = 100;
+
+#[get("/content/deletions")]
+async fn get_content_deletions(
+    state: Data<AppState>,
+    Query(pagination): Query<PaginationParameters>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let total = state.services.content.get_deletions_total().await?;
+    let entries = state
+        .services
+        .content
+        .list_deletions(
+            pagination.limit.unwrap_or(DEFAULT_INDEX_LIMIT) as i64,
+            pagination.offset.unwrap_or(0) as i64,
+        )
+        .await?;
+
+    Ok(Json(PaginatedResponse::new(total, entries)))
+}
+
+#[get("/content/{kind}")]
+async fn get_content(
+    state: Data<AppState>,
+    kind: Path<(ContentKind,)>,
+    Query(pagination): Query<PaginationParameters>,
+    Query(player): Query<PlayerParameters>,
+    Query(mut filter): Query<ContentFilter>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let kind = kind.into_inner().0;
+    if !kind.has_ratings() && (filter.min_rating.is_some() || filter.max_rating.is_some()) {
+        return Ok(HttpResponse::BadRequest().body("Only blood messages have ratings"));
+    }
+
+    filter.player_external_id = player.external_id.map(player_external_id);
+
+    let total = state.services.content.get_total(kind, &filter).await?;
+    let entries = state
+        .services
+        .content
+        .list_content(
+            kind,
+            &filter,
+            pagination.limit.unwrap_or(DEFAULT_INDEX_LIMIT) as i64,
+            pagination.offset.unwrap_or(0) as i64,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(PaginatedResponse::new(total, entries)))
+}
+
+#[get("/content/{kind}/{content_id}")]
+async fn get_content_by_id(
+    state: Data<AppState>,
+    path: Path<(ContentKind, i64)>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let (kind, content_id) = path.into_inner();
+    let content = state.services.content.get_content(kind, content_id).await?;
+
+    match content {
+        Some(content) => Ok(HttpResponse::Ok().json(content)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[delete("/content/{kind}/{content_id}")]
+async fn delete_content(
+    state: Data<AppState>,
+    moderator: ReqData<ApiKeyLabel>,
+    path: Path<(ContentKind, i64)>,
+    Query(params): Query<DeleteContentParameters>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let (kind, content_id) = path.into_inner();
+    let deleted = state
+        .services
+        .content
+        .delete_content(kind, content_id, params.reason.as_deref(), &moderator.0)
+        .await?;
+
+    Ok(Json(deleted))
+}
+
+/// Wipes everything a player has left in the world, eg. after banning a griefer.
+#[delete("/player/{external_id}/content")]
+async fn delete_player_content(
+    state: Data<AppState>,
+    moderator: ReqData<ApiKeyLabel>,
+    external_id: Path<(u64,)>,
+    Query(params): Query<DeleteContentParameters>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let external_id = player_external_id(external_id.into_inner().0);
+    let deleted = state
+        .services
+        .content
+        .delete_player_content(&external_id, params.reason.as_deref(), &moderator.0)
+        .await?
+        .into_iter()
+        .map(|(kind, count)| (kind.as_str(), count))
+        .collect::<HashMap<_, _>>();
+
+    Ok(Json(deleted))
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerParameters {
+    /// Only list content by the player with this steam ID.
+    external_id: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteContentParameters {
+    reason: Option<String>,
+}
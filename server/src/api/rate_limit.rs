@@ -0,0 +1,41 @@
+use actix_web::{
+    get,
+    web::{Data, Json, Query},
+    Responder,
+};
+use serde::Deserialize;
+
+use crate::api::{
+    ban::{PaginatedResponse, PaginationParameters},
+    AppState,
+};
+
+const DEFAULT_INDEX_LIMIT: i32 = 100;
+
+/// Players that had requests refused for going over their rate limit, worst first.
+#[get("/ratelimit/offenders")]
+async fn get_rate_limit_offenders(
+    state: Data<AppState>,
+    Query(pagination): Query<PaginationParameters>,
+    Query(filter): Query<OffenderFilterParameters>,
+) -> impl Responder {
+    let offenders = state
+        .services
+        .rate_limiter
+        .offenders(filter.min_rejected.unwrap_or(1));
+
+    let total = offenders.len() as i64;
+    let entries = offenders
+        .into_iter()
+        .skip(pagination.offset.unwrap_or(0).max(0) as usize)
+        .take(pagination.limit.unwrap_or(DEFAULT_INDEX_LIMIT).max(0) as usize)
+        .collect::<Vec<_>>();
+
+    Json(PaginatedResponse::new(total, entries))
+}
+
+#[derive(Debug, Deserialize)]
+struct OffenderFilterParameters {
+    /// Only list players with at least this many refused requests.
+    min_rejected: Option<u64>,
+}
@@ -0,0 +1,109 @@
+use std::{
+    error::Error,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    delete, get, post,
+    web::{Data, Json, Path, Query, ReqData},
+    HttpResponse, Responder,
+};
+use serde::Deserialize;
+
+use crate::{
+    api::{
+        auth::ApiKeyLabel,
+        ban::{PaginatedResponse, PaginationParameters},
+        AppState,
+    },
+    restrictions::Restriction,
+};
+
+const DEFAULT_INDEX_LIMIT: i32 = 100;
+
+#[get("/restriction")]
+async fn get_restrictions(
+    state: Data<AppState>,
+    Query(pagination): Query<PaginationParameters>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let total = state.services.restrictions.get_total().await?;
+    let entries = state
+        .services
+        .restrictions
+        .list_restrictions(
+            pagination.limit.unwrap_or(DEFAULT_INDEX_LIMIT) as i64,
+            pagination.offset.unwrap_or(0) as i64,
+        )
+        .await?;
+
+    Ok(Json(PaginatedResponse::new(total, entries)))
+}
+
+#[get("/restriction/{external_id}")]
+async fn get_restrictions_by_id(
+    state: Data<AppState>,
+    external_id: Path<(String,)>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let external_id = &external_id.into_inner().0;
+    let restrictions = state
+        .services
+        .restrictions
+        .get_restrictions(external_id)
+        .await?;
+
+    Ok(Json(restrictions))
+}
+
+#[post("/restriction")]
+async fn post_restriction(
+    state: Data<AppState>,
+    moderator: ReqData<ApiKeyLabel>,
+    request: Json<NewRestriction>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    if request
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+    {
+        return Ok(HttpResponse::BadRequest().body("expires_at lies in the past"));
+    }
+
+    state
+        .services
+        .restrictions
+        .add_restriction(
+            &request.external_id,
+            request.restriction,
+            request.reason.as_deref(),
+            request.expires_at,
+            &moderator.0,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(true))
+}
+
+#[delete("/restriction/{external_id}/{restriction}")]
+async fn delete_restriction(
+    state: Data<AppState>,
+    path: Path<(String, Restriction)>,
+) -> Result<impl Responder, Box<dyn Error>> {
+    let (external_id, restriction) = path.into_inner();
+    let deleted = state
+        .services
+        .restrictions
+        .delete_restriction(&external_id, restriction)
+        .await?;
+
+    Ok(Json(deleted))
+}
+
+#[derive(Debug, Deserialize)]
+struct NewRestriction {
+    external_id: String,
+    restriction: Restriction,
+    /// Free-text reason shown to other moderators.
+    reason: Option<String>,
+    /// Unix timestamp at which the restriction lapses. Omit for a permanent restriction.
+    expires_at: Option<i64>,
+}
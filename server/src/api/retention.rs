@@ -0,0 +1,12 @@
+use actix_web::{get, web::Data, HttpResponse, Responder};
+
+use crate::api::AppState;
+
+/// Stats of the most recent pruning run. Empty until the first run finished.
+#[get("/retention")]
+async fn get_retention(state: Data<AppState>) -> impl Responder {
+    match state.services.retention.last_run() {
+        Some(run) => HttpResponse::Ok().json(run),
+        None => HttpResponse::NoContent().finish(),
+    }
+}
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpResponse, Responder,
+};
+use serde::Deserialize;
+
+use super::AppState;
+
+/// Gracefully shuts the server down. Connected clients are notified and given the grace period to
+/// leave before being disconnected.
+#[post("/shutdown")]
+pub async fn post_shutdown(
+    state: Data<AppState>,
+    request: Json<ShutdownRequest>,
+) -> impl Responder {
+    let request = request.into_inner();
+    let started = state.shutdown.initiate(
+        request.grace_period.map(Duration::from_secs),
+        request.message,
+    );
+
+    if started {
+        HttpResponse::Ok().json(true)
+    } else {
+        HttpResponse::Conflict().json(false)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ShutdownRequest {
+    /// Seconds to wait for clients to leave by themselves.
+    grace_period: Option<u64>,
+    /// Announcement to broadcast, `{minutes}` is replaced by the grace period in minutes.
+    message: Option<String>,
+}
@@ -1,6 +1,11 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Postgres, Row};
 
+/// Bans that haven't run out yet. Bans without an expiry are permanent.
+const ACTIVE_CONDITION: &str = "(expires_at IS NULL OR expires_at > EXTRACT(EPOCH FROM NOW()))";
+const EXPIRED_CONDITION: &str =
+    "(expires_at IS NOT NULL AND expires_at <= EXTRACT(EPOCH FROM NOW()))";
+
 pub struct BanService {
     pub database: Pool<Postgres>,
 }
@@ -10,54 +15,209 @@ impl BanService {
         Self { database }
     }
 
-    pub async fn add_ban(&self, external_id: &str) -> Result<i64, sqlx::Error> {
-        let ban_id = sqlx::query("INSERT INTO bans (external_id) VALUES ($1) RETURNING ban_id")
-            .bind(external_id)
-            .fetch_one(&self.database)
-            .await?
-            .get("ban_id");
+    /// Bans a player, replacing any ban already on record for them. Omitting the expiry makes the
+    /// ban permanent.
+    pub async fn add_ban(
+        &self,
+        external_id: &str,
+        reason: Option<&str>,
+        expires_at: Option<i64>,
+        moderator: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let mut transaction = self.database.begin().await?;
+
+        let ban_id = sqlx::query(
+            "INSERT INTO bans (external_id, reason, expires_at, moderator)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (external_id) DO UPDATE SET
+                banned_at = EXTRACT(EPOCH FROM NOW()),
+                reason = EXCLUDED.reason,
+                expires_at = EXCLUDED.expires_at,
+                moderator = EXCLUDED.moderator
+            RETURNING ban_id",
+        )
+        .bind(external_id)
+        .bind(reason)
+        .bind(expires_at)
+        .bind(moderator)
+        .fetch_one(&mut *transaction)
+        .await?
+        .get("ban_id");
 
+        Self::record_event(
+            &mut transaction,
+            external_id,
+            BanAction::Ban,
+            moderator,
+            reason,
+            expires_at,
+        )
+        .await?;
+
+        transaction.commit().await?;
         Ok(ban_id)
     }
 
-    pub async fn delete_ban(&self, external_id: &str) -> Result<bool, sqlx::Error> {
+    /// Lifts a player's ban. Returns false if there was no ban to lift.
+    pub async fn delete_ban(
+        &self,
+        external_id: &str,
+        reason: Option<&str>,
+        moderator: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let mut transaction = self.database.begin().await?;
+
         let rows_affected = sqlx::query("DELETE FROM bans WHERE external_id = $1")
             .bind(external_id)
-            .execute(&self.database)
+            .execute(&mut *transaction)
             .await?
             .rows_affected();
 
+        if rows_affected > 0 {
+            Self::record_event(
+                &mut transaction,
+                external_id,
+                BanAction::Unban,
+                moderator,
+                reason,
+                None,
+            )
+            .await?;
+        }
+
+        transaction.commit().await?;
         Ok(rows_affected > 0)
     }
 
+    /// Retrieves the player's ban if it is still in effect.
     pub async fn get_ban(&self, external_id: &str) -> Result<Option<BanRecord>, sqlx::Error> {
-        let ban = sqlx::query_as::<_, BanRecord>("SELECT * FROM bans WHERE external_id = $1")
-            .bind(external_id)
-            .fetch_optional(&self.database)
-            .await?;
+        let ban = sqlx::query_as::<_, BanRecord>(&format!(
+            "SELECT * FROM bans WHERE external_id = $1 AND {ACTIVE_CONDITION}"
+        ))
+        .bind(external_id)
+        .fetch_optional(&self.database)
+        .await?;
 
         Ok(ban)
     }
 
-    pub async fn list_bans(&self, limit: i64, offset: i64) -> Result<Vec<BanRecord>, sqlx::Error> {
-        let bans =
-            sqlx::query_as::<_, BanRecord>("SELECT * FROM bans ORDER BY ban_id LIMIT $1 OFFSET $2")
-                .bind(limit)
-                .bind(offset)
-                .fetch_all(&self.database)
-                .await?;
+    pub async fn list_bans(
+        &self,
+        status: BanStatus,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<BanRecord>, sqlx::Error> {
+        let bans = sqlx::query_as::<_, BanRecord>(&format!(
+            "SELECT * FROM bans WHERE {} ORDER BY ban_id LIMIT $1 OFFSET $2",
+            status.condition()
+        ))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.database)
+        .await?;
 
         Ok(bans)
     }
 
-    pub async fn get_total(&self) -> Result<i64, sqlx::Error> {
-        let count = sqlx::query("SELECT COUNT(*) FROM bans")
+    pub async fn get_total(&self, status: BanStatus) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query(&format!(
+            "SELECT COUNT(*) FROM bans WHERE {}",
+            status.condition()
+        ))
+        .fetch_one(&self.database)
+        .await?
+        .get(0);
+
+        Ok(count)
+    }
+
+    /// Retrieves the ban history of a player, most recent first.
+    pub async fn list_events(
+        &self,
+        external_id: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<BanEventRecord>, sqlx::Error> {
+        let events = sqlx::query_as::<_, BanEventRecord>(
+            "SELECT * FROM ban_events WHERE external_id = $1
+            ORDER BY ban_event_id DESC LIMIT $2 OFFSET $3",
+        )
+        .bind(external_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(events)
+    }
+
+    pub async fn get_events_total(&self, external_id: &str) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query("SELECT COUNT(*) FROM ban_events WHERE external_id = $1")
+            .bind(external_id)
             .fetch_one(&self.database)
             .await?
             .get(0);
 
         Ok(count)
     }
+
+    async fn record_event(
+        transaction: &mut sqlx::Transaction<'_, Postgres>,
+        external_id: &str,
+        action: BanAction,
+        moderator: &str,
+        reason: Option<&str>,
+        expires_at: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO ban_events (external_id, action, moderator, reason, expires_at)
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(external_id)
+        .bind(action.as_str())
+        .bind(moderator)
+        .bind(reason)
+        .bind(expires_at)
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Filter for listing bans.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BanStatus {
+    #[default]
+    All,
+    Active,
+    Expired,
+}
+
+impl BanStatus {
+    fn condition(&self) -> &'static str {
+        match self {
+            BanStatus::All => "TRUE",
+            BanStatus::Active => ACTIVE_CONDITION,
+            BanStatus::Expired => EXPIRED_CONDITION,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BanAction {
+    Ban,
+    Unban,
+}
+
+impl BanAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BanAction::Ban => "ban",
+            BanAction::Unban => "unban",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
@@ -65,4 +225,18 @@ pub struct BanRecord {
     pub ban_id: i64,
     pub external_id: String,
     pub banned_at: i64,
+    pub expires_at: Option<i64>,
+    pub reason: Option<String>,
+    pub moderator: Option<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct BanEventRecord {
+    pub ban_event_id: i64,
+    pub external_id: String,
+    pub action: String,
+    pub moderator: Option<String>,
+    pub reason: Option<String>,
+    pub expires_at: Option<i64>,
+    pub created_at: i64,
 }
@@ -0,0 +1,342 @@
+use std::time::Duration;
+
+use message::eldenring::RequestUpdatePlayerStatusParams;
+use serde::Serialize;
+use sqlx::{Pool, Postgres, Row};
+
+const INSERT_SNAPSHOT_QUERY: &str = "
+    INSERT INTO character_snapshots (
+        character_id,
+        player_id,
+        play_region,
+        level,
+        vigor,
+        mind,
+        endurance,
+        vitality,
+        strength,
+        dexterity,
+        intelligence,
+        faith,
+        arcane,
+        runes_owned,
+        max_reinforce_level,
+        weapons_left_hand,
+        weapons_right_hand,
+        protectors,
+        accessories,
+        spells,
+        game_clear_count,
+        death_count,
+        total_summon_count,
+        coop_success_count,
+        invaders_killed_count,
+        hosts_killed_count,
+        play_time,
+        owned_dlcs,
+        visited_areas,
+        regulation_version
+    ) VALUES (
+        $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15,
+        $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30
+    ) RETURNING character_snapshot_id";
+
+/// Keeps track of the characters players log in with so content can be traced back to the
+/// character that created it. Also holds on to snapshots of the characters' status.
+pub struct CharacterService {
+    pub database: Pool<Postgres>,
+    /// Minimum time between two snapshots of the same character.
+    pub snapshot_interval: Duration,
+}
+
+impl CharacterService {
+    pub fn new(database: Pool<Postgres>, snapshot_interval: Duration) -> Self {
+        Self {
+            database,
+            snapshot_interval,
+        }
+    }
+
+    /// Character the player is logging in with. The login doesn't tell which character that is
+    /// so this is the character the player was last seen on, which keeps its ID across logins for
+    /// as long as the player sticks to it. Players without characters get one.
+    pub async fn login_character(&self, player_id: i32) -> Result<CharacterRecord, sqlx::Error> {
+        sqlx::query_as::<_, CharacterRecord>(
+            "WITH last_seen AS (
+                UPDATE characters SET last_seen_at = EXTRACT(EPOCH FROM NOW())
+                WHERE character_id = (
+                    SELECT character_id FROM characters
+                    WHERE player_id = $1
+                    ORDER BY last_seen_at DESC, character_id DESC
+                    LIMIT 1
+                )
+                RETURNING *
+            ), created AS (
+                INSERT INTO characters (player_id)
+                SELECT $1 WHERE NOT EXISTS (SELECT 1 FROM last_seen)
+                RETURNING *
+            )
+            SELECT * FROM last_seen
+            UNION ALL
+            SELECT * FROM created",
+        )
+        .bind(player_id)
+        .fetch_one(&self.database)
+        .await
+    }
+
+    /// Character of the player going by the name. Names the current character if it has no name
+    /// yet. Otherwise the player switched characters, which is the one by that name they were
+    /// last seen on or a new one. Names aren't unique, a character that is renamed or shares its
+    /// name only keeps its ID through [Self::login_character].
+    pub async fn resolve_character(
+        &self,
+        player_id: i32,
+        current: Option<i32>,
+        name: &str,
+    ) -> Result<i32, sqlx::Error> {
+        let mut transaction = self.database.begin().await?;
+
+        let named = sqlx::query(
+            "UPDATE characters SET name = $3, last_seen_at = EXTRACT(EPOCH FROM NOW())
+            WHERE character_id = $2 AND player_id = $1 AND name IS NULL
+            RETURNING character_id",
+        )
+        .bind(player_id)
+        .bind(current)
+        .bind(name)
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        let existing = match named {
+            Some(row) => Some(row),
+            None => {
+                sqlx::query(
+                    "UPDATE characters SET last_seen_at = EXTRACT(EPOCH FROM NOW())
+                    WHERE character_id = (
+                        SELECT character_id FROM characters
+                        WHERE player_id = $1 AND name = $2
+                        ORDER BY last_seen_at DESC, character_id DESC
+                        LIMIT 1
+                    )
+                    RETURNING character_id",
+                )
+                .bind(player_id)
+                .bind(name)
+                .fetch_optional(&mut *transaction)
+                .await?
+            }
+        };
+
+        let character_id = match existing {
+            Some(row) => row.get("character_id"),
+            None => sqlx::query(
+                "INSERT INTO characters (player_id, name) VALUES ($1, $2)
+                RETURNING character_id",
+            )
+            .bind(player_id)
+            .bind(name)
+            .fetch_one(&mut *transaction)
+            .await?
+            .get("character_id"),
+        };
+
+        transaction.commit().await?;
+        Ok(character_id)
+    }
+
+    /// Characters of the player with the given players table external ID, most recently seen
+    /// first.
+    pub async fn list_characters(
+        &self,
+        player_external_id: &str,
+    ) -> Result<Vec<CharacterRecord>, sqlx::Error> {
+        sqlx::query_as::<_, CharacterRecord>(
+            "SELECT * FROM characters
+            WHERE player_id IN (SELECT player_id FROM players WHERE external_id = $1)
+            ORDER BY last_seen_at DESC",
+        )
+        .bind(player_external_id)
+        .fetch_all(&self.database)
+        .await
+    }
+
+    pub async fn get_character(
+        &self,
+        character_id: i32,
+    ) -> Result<Option<CharacterRecord>, sqlx::Error> {
+        sqlx::query_as::<_, CharacterRecord>("SELECT * FROM characters WHERE character_id = $1")
+            .bind(character_id)
+            .fetch_optional(&self.database)
+            .await
+    }
+
+    /// Stores the character's status as reported by the client.
+    pub async fn record_snapshot(
+        &self,
+        player_id: i32,
+        character_id: i32,
+        status: &RequestUpdatePlayerStatusParams,
+    ) -> Result<i64, sqlx::Error> {
+        let character = &status.character;
+        let attributes = &character.attributes;
+        let equipment = &character.equipment;
+
+        Ok(sqlx::query(INSERT_SNAPSHOT_QUERY)
+            .bind(character_id)
+            .bind(player_id)
+            .bind(status.play_region as i32)
+            .bind(character.level as i32)
+            .bind(attributes.vigor as i32)
+            .bind(attributes.mind as i32)
+            .bind(attributes.endurance as i32)
+            .bind(attributes.vitality as i32)
+            .bind(attributes.strength as i32)
+            .bind(attributes.dexterity as i32)
+            .bind(attributes.intelligence as i32)
+            .bind(attributes.faith as i32)
+            .bind(attributes.arcane as i32)
+            .bind(character.runes_owned as i64)
+            .bind(character.max_reinforce_level as i32)
+            .bind(
+                equipment
+                    .weapons_left_hand
+                    .iter()
+                    .map(|w| w.weapon as i32)
+                    .collect::<Vec<_>>(),
+            )
+            .bind(
+                equipment
+                    .weapons_right_hand
+                    .iter()
+                    .map(|w| w.weapon as i32)
+                    .collect::<Vec<_>>(),
+            )
+            .bind(
+                [
+                    &equipment.head,
+                    &equipment.chest,
+                    &equipment.arms,
+                    &equipment.legs,
+                ]
+                .iter()
+                .map(|p| p.protector as i32)
+                .collect::<Vec<_>>(),
+            )
+            .bind(&equipment.accessories)
+            .bind(&equipment.spells)
+            .bind(status.game_clear_count as i32)
+            .bind(status.death_count as i32)
+            .bind(status.total_summon_count as i32)
+            .bind(status.coop_success_count as i32)
+            .bind(status.invaders_killed_count as i32)
+            .bind(status.hosts_killed_count as i32)
+            .bind(character.statistics.play_time as i32)
+            .bind(
+                character
+                    .owned_dlcs
+                    .iter()
+                    .map(|d| *d as i32)
+                    .collect::<Vec<_>>(),
+            )
+            .bind(
+                character
+                    .visited_areas
+                    .iter()
+                    .map(|a| *a as i32)
+                    .collect::<Vec<_>>(),
+            )
+            .bind(character.regulation_version as i64)
+            .fetch_one(&self.database)
+            .await?
+            .get("character_snapshot_id"))
+    }
+
+    pub async fn latest_snapshot(
+        &self,
+        character_id: i32,
+    ) -> Result<Option<CharacterSnapshotRecord>, sqlx::Error> {
+        sqlx::query_as::<_, CharacterSnapshotRecord>(
+            "SELECT * FROM character_snapshots WHERE character_id = $1
+            ORDER BY created_at DESC, character_snapshot_id DESC LIMIT 1",
+        )
+        .bind(character_id)
+        .fetch_optional(&self.database)
+        .await
+    }
+
+    /// Snapshots of the character, newest first.
+    pub async fn list_snapshots(
+        &self,
+        character_id: i32,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<CharacterSnapshotRecord>, sqlx::Error> {
+        sqlx::query_as::<_, CharacterSnapshotRecord>(
+            "SELECT * FROM character_snapshots WHERE character_id = $1
+            ORDER BY created_at DESC, character_snapshot_id DESC LIMIT $2 OFFSET $3",
+        )
+        .bind(character_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.database)
+        .await
+    }
+
+    pub async fn get_snapshots_total(&self, character_id: i32) -> Result<i64, sqlx::Error> {
+        Ok(
+            sqlx::query("SELECT COUNT(*) FROM character_snapshots WHERE character_id = $1")
+                .bind(character_id)
+                .fetch_one(&self.database)
+                .await?
+                .get(0),
+        )
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CharacterRecord {
+    pub character_id: i32,
+    pub player_id: i32,
+    /// Unknown until the first status update of the character came in.
+    pub name: Option<String>,
+    pub created_at: i64,
+    pub last_seen_at: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CharacterSnapshotRecord {
+    pub character_snapshot_id: i64,
+    pub character_id: i32,
+    pub player_id: i32,
+    pub play_region: i32,
+    pub level: i32,
+    pub vigor: i32,
+    pub mind: i32,
+    pub endurance: i32,
+    pub vitality: i32,
+    pub strength: i32,
+    pub dexterity: i32,
+    pub intelligence: i32,
+    pub faith: i32,
+    pub arcane: i32,
+    pub runes_owned: i64,
+    pub max_reinforce_level: i32,
+    pub weapons_left_hand: Vec<i32>,
+    pub weapons_right_hand: Vec<i32>,
+    /// Head, chest, arms and legs.
+    pub protectors: Vec<i32>,
+    pub accessories: Vec<i32>,
+    pub spells: Vec<i32>,
+    pub game_clear_count: i32,
+    pub death_count: i32,
+    pub total_summon_count: i32,
+    pub coop_success_count: i32,
+    pub invaders_killed_count: i32,
+    pub hosts_killed_count: i32,
+    pub play_time: i32,
+    pub owned_dlcs: Vec<i32>,
+    pub visited_areas: Vec<i32>,
+    pub regulation_version: i64,
+    pub created_at: i64,
+}
@@ -0,0 +1,361 @@
+use std::{collections::BTreeMap, fs::File};
+
+use message::eldenring::{CharacterData, CharacterDataAttributes};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres, Row};
+
+/// Only read on startup.
+const CHEAT_RULES: &str = "config/cheat_rules.yml";
+
+/// Moderator recorded on bans handed out by the cheat rules.
+pub const CHEAT_RULES_MODERATOR: &str = "system";
+
+const MAX_LEVEL: u32 = 713;
+const MAX_ATTRIBUTE: u32 = 99;
+const MAX_REINFORCE_LEVEL: u32 = 25;
+const MAX_RUNES_OWNED: u32 = 999_999_999;
+
+/// The attributes of every archetype add up to its starting level plus this, and every level
+/// gained raises a single attribute by one.
+const ATTRIBUTE_LEVEL_OFFSET: u32 = 79;
+
+/// A state the game can't legitimately end up in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheatRule {
+    /// Level below 1 or above 713.
+    LevelOutOfRange,
+    /// Attribute below 1 or above 99.
+    AttributeOutOfRange,
+    /// Attributes don't add up to what the level allows for.
+    AttributeLevelMismatch,
+    /// Weapon reinforced beyond +25.
+    ReinforceLevelOutOfRange,
+    /// More runes held than the game can count.
+    RunesOutOfRange,
+}
+
+impl CheatRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheatRule::LevelOutOfRange => "level_out_of_range",
+            CheatRule::AttributeOutOfRange => "attribute_out_of_range",
+            CheatRule::AttributeLevelMismatch => "attribute_level_mismatch",
+            CheatRule::ReinforceLevelOutOfRange => "reinforce_level_out_of_range",
+            CheatRule::RunesOutOfRange => "runes_out_of_range",
+        }
+    }
+
+    /// Describes what's off about the character if it breaks the rule.
+    pub fn check(&self, character: &CharacterData) -> Option<String> {
+        match self {
+            CheatRule::LevelOutOfRange => (!(1..=MAX_LEVEL).contains(&character.level))
+                .then(|| format!("level {} outside of 1..={MAX_LEVEL}", character.level)),
+
+            CheatRule::AttributeOutOfRange => {
+                let offending = attributes(&character.attributes)
+                    .into_iter()
+                    .filter(|(_, value)| !(1..=MAX_ATTRIBUTE).contains(value))
+                    .map(|(name, value)| format!("{name} {value}"))
+                    .collect::<Vec<_>>();
+
+                (!offending.is_empty())
+                    .then(|| format!("{} outside of 1..={MAX_ATTRIBUTE}", offending.join(", ")))
+            }
+
+            CheatRule::AttributeLevelMismatch => {
+                let sum = attributes(&character.attributes)
+                    .iter()
+                    .map(|(_, value)| *value as u64)
+                    .sum::<u64>();
+                let expected = character.level as u64 + ATTRIBUTE_LEVEL_OFFSET as u64;
+
+                (sum != expected).then(|| {
+                    format!(
+                        "attributes add up to {sum}, level {} calls for {expected}",
+                        character.level
+                    )
+                })
+            }
+
+            CheatRule::ReinforceLevelOutOfRange => {
+                (character.max_reinforce_level > MAX_REINFORCE_LEVEL).then(|| {
+                    format!(
+                        "max reinforce level {} above {MAX_REINFORCE_LEVEL}",
+                        character.max_reinforce_level
+                    )
+                })
+            }
+
+            CheatRule::RunesOutOfRange => (character.runes_owned > MAX_RUNES_OWNED).then(|| {
+                format!(
+                    "{} runes held, above {MAX_RUNES_OWNED}",
+                    character.runes_owned
+                )
+            }),
+        }
+    }
+}
+
+/// The attributes players level up. Vitality is left out as the game doesn't use it.
+fn attributes(attributes: &CharacterDataAttributes) -> [(&'static str, u32); 8] {
+    [
+        ("vigor", attributes.vigor),
+        ("mind", attributes.mind),
+        ("endurance", attributes.endurance),
+        ("strength", attributes.strength),
+        ("dexterity", attributes.dexterity),
+        ("intelligence", attributes.intelligence),
+        ("faith", attributes.faith),
+        ("arcane", attributes.arcane),
+    ]
+}
+
+/// What to do with a player whose character breaks a rule. Every broken rule is flagged
+/// regardless of the action.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheatAction {
+    /// Only flag the player.
+    #[default]
+    Log,
+    /// Close the player's connection.
+    Disconnect,
+    /// Ban the player permanently.
+    Ban,
+}
+
+impl CheatAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheatAction::Log => "log",
+            CheatAction::Disconnect => "disconnect",
+            CheatAction::Ban => "ban",
+        }
+    }
+}
+
+/// Rules characters are checked against along with the action for each. Rules that aren't
+/// listed aren't checked.
+#[derive(Debug, Default, Deserialize)]
+pub struct CheatRuleConfig {
+    #[serde(default)]
+    pub rules: BTreeMap<CheatRule, CheatAction>,
+}
+
+impl CheatRuleConfig {
+    /// Rules the character breaks. Status updates sent before a character is loaded report all
+    /// zeroes, those aren't held against the player.
+    pub fn check(&self, character: &CharacterData) -> Vec<CheatViolation> {
+        if character.character_name.0.is_empty() || character.level == 0 {
+            return Vec::new();
+        }
+
+        self.rules
+            .iter()
+            .filter_map(|(rule, action)| {
+                rule.check(character).map(|evidence| CheatViolation {
+                    rule: *rule,
+                    action: *action,
+                    evidence,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A rule broken by a character.
+#[derive(Debug)]
+pub struct CheatViolation {
+    pub rule: CheatRule,
+    pub action: CheatAction,
+    pub evidence: String,
+}
+
+/// Checks the stats players report for their characters and keeps track of players that were
+/// flagged for impossible ones.
+pub struct CheatService {
+    pub database: Pool<Postgres>,
+    pub config: CheatRuleConfig,
+}
+
+impl CheatService {
+    pub fn new(database: Pool<Postgres>) -> Self {
+        let config = File::open(CHEAT_RULES)
+            .map_err(Box::<dyn std::error::Error>::from)
+            .and_then(|file| Ok(serde_yaml::from_reader(file)?))
+            .unwrap_or_else(|e| {
+                log::warn!("Could not load cheat rules, characters won't be checked: {e}");
+                CheatRuleConfig::default()
+            });
+
+        Self { database, config }
+    }
+
+    /// Rules the character breaks.
+    pub fn check(&self, character: &CharacterData) -> Vec<CheatViolation> {
+        self.config.check(character)
+    }
+
+    /// Records the violation against the player.
+    pub async fn flag(
+        &self,
+        external_id: &str,
+        player_id: i32,
+        character_id: Option<i32>,
+        violation: &CheatViolation,
+    ) -> Result<i64, sqlx::Error> {
+        Ok(sqlx::query(
+            "INSERT INTO cheat_flags (external_id, player_id, character_id, rule, evidence, action)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING cheat_flag_id",
+        )
+        .bind(external_id)
+        .bind(player_id)
+        .bind(character_id)
+        .bind(violation.rule.as_str())
+        .bind(&violation.evidence)
+        .bind(violation.action.as_str())
+        .fetch_one(&self.database)
+        .await?
+        .get("cheat_flag_id"))
+    }
+
+    /// Flags, most recent first. Narrowed down to a single player if an external ID is given.
+    pub async fn list_flags(
+        &self,
+        external_id: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<CheatFlagRecord>, sqlx::Error> {
+        sqlx::query_as::<_, CheatFlagRecord>(
+            "SELECT * FROM cheat_flags WHERE ($1::VARCHAR IS NULL OR external_id = $1)
+            ORDER BY cheat_flag_id DESC LIMIT $2 OFFSET $3",
+        )
+        .bind(external_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.database)
+        .await
+    }
+
+    pub async fn get_flags_total(&self, external_id: Option<&str>) -> Result<i64, sqlx::Error> {
+        Ok(sqlx::query(
+            "SELECT COUNT(*) FROM cheat_flags WHERE ($1::VARCHAR IS NULL OR external_id = $1)",
+        )
+        .bind(external_id)
+        .fetch_one(&self.database)
+        .await?
+        .get(0))
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CheatFlagRecord {
+    pub cheat_flag_id: i64,
+    pub external_id: String,
+    pub player_id: i32,
+    pub character_id: Option<i32>,
+    pub rule: String,
+    pub evidence: String,
+    pub action: String,
+    pub created_at: i64,
+}
+
+#[cfg(test)]
+mod test {
+    use message::eldenring::{CharacterData, RequestUpdatePlayerStatusParams};
+    use wire::deserialize;
+
+    use super::{CheatAction, CheatRule, CheatRuleConfig};
+
+    const RULES: [CheatRule; 5] = [
+        CheatRule::LevelOutOfRange,
+        CheatRule::AttributeOutOfRange,
+        CheatRule::AttributeLevelMismatch,
+        CheatRule::ReinforceLevelOutOfRange,
+        CheatRule::RunesOutOfRange,
+    ];
+
+    fn character() -> CharacterData {
+        let status: RequestUpdatePlayerStatusParams = deserialize(include_bytes!(
+            "../../message/test/data/RequestUpdatePlayerStatus.bin"
+        ))
+        .unwrap();
+
+        status.character
+    }
+
+    #[test]
+    fn accepts_legitimate_character() {
+        let character = character();
+
+        for rule in RULES {
+            assert_eq!(rule.check(&character), None, "{rule:?}");
+        }
+    }
+
+    #[test]
+    fn flags_attributes_not_matching_level() {
+        let mut character = character();
+        character.attributes.arcane += 10;
+
+        assert_eq!(
+            CheatRule::AttributeLevelMismatch.check(&character).unwrap(),
+            "attributes add up to 253, level 164 calls for 243"
+        );
+        assert_eq!(CheatRule::AttributeOutOfRange.check(&character), None);
+    }
+
+    #[test]
+    fn flags_out_of_range_stats() {
+        let mut character = character();
+        character.level = 800;
+        character.attributes.vigor = 120;
+        character.max_reinforce_level = 30;
+        character.runes_owned = u32::MAX;
+
+        for rule in RULES {
+            assert!(rule.check(&character).is_some(), "{rule:?}");
+        }
+        assert_eq!(
+            CheatRule::AttributeOutOfRange.check(&character).unwrap(),
+            "vigor 120 outside of 1..=99"
+        );
+    }
+
+    #[test]
+    fn skips_character_that_isnt_loaded() {
+        let config: CheatRuleConfig = serde_yaml::from_str(
+            "
+            rules:
+              level_out_of_range: ban
+              attribute_level_mismatch: ban
+            ",
+        )
+        .unwrap();
+
+        let mut character = character();
+        character.attributes.arcane += 10;
+        assert_eq!(config.check(&character).len(), 1);
+
+        let mut unloaded = character.clone();
+        unloaded.character_name.0.clear();
+        unloaded.level = 0;
+        assert!(config.check(&unloaded).is_empty());
+
+        character.level = 0;
+        assert!(config.check(&character).is_empty());
+    }
+
+    #[test]
+    fn parses_bundled_config() {
+        let config: CheatRuleConfig =
+            serde_yaml::from_str(include_str!("../../config/cheat_rules.yml")).unwrap();
+
+        assert!(config
+            .rules
+            .values()
+            .all(|action| *action == CheatAction::Log));
+    }
+}
@@ -0,0 +1,301 @@
+use std::{fs::File, path::Path};
+
+use dashmap::DashMap;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request},
+    http::StatusCode,
+};
+
+/// Header the Waygate client announces its version in.
+pub const CLIENT_VERSION_HEADER: &str = "x-waygate-client-version";
+
+#[derive(Debug, Error)]
+pub enum ClientVersionError {
+    #[error("Upgrade request missing {CLIENT_VERSION_HEADER} header.")]
+    Missing,
+    #[error("Upgrade request has more than one {CLIENT_VERSION_HEADER} header.")]
+    Duplicate,
+    #[error("Upgrade request has malformed {CLIENT_VERSION_HEADER} header.")]
+    Malformed,
+    #[error("Waygate client {0} is no longer supported, please update to version {1} or newer.")]
+    Outdated(Version, Version),
+    #[error(
+        "Waygate client {0} is not supported by this server, please update your Waygate client."
+    )]
+    Denied(Version),
+}
+
+impl ClientVersionError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ClientVersionError::Outdated(..) | ClientVersionError::Denied(_) => {
+                StatusCode::UPGRADE_REQUIRED
+            }
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// How clients of a certain version are treated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionPolicy {
+    /// Client plays as usual.
+    #[default]
+    Allowed,
+    /// Client is turned away during the websocket upgrade.
+    Denied,
+    /// Client connects but is only served an announcement asking them to update.
+    Degraded,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClientVersionRule {
+    pub range: VersionReq,
+    pub policy: VersionPolicy,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ClientVersionConfig {
+    /// Policy for versions matching none of the rules.
+    #[serde(default)]
+    pub default: VersionPolicy,
+    /// Checked in order, the first rule with a matching range applies.
+    #[serde(default)]
+    pub rules: Vec<ClientVersionRule>,
+}
+
+/// Amount of connected clients running a version.
+#[derive(Clone, Debug, Serialize)]
+pub struct ClientVersionCount {
+    pub version: Version,
+    pub policy: VersionPolicy,
+    pub connections: usize,
+}
+
+/// Applies the version policies and keeps track of the versions of connected clients.
+#[derive(Default)]
+pub struct ClientVersions {
+    config: ClientVersionConfig,
+    connected: DashMap<Version, usize>,
+}
+
+impl ClientVersions {
+    pub fn new(config: ClientVersionConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::new(serde_yaml::from_reader(File::open(path)?)?))
+    }
+
+    pub fn policy(&self, version: &Version) -> VersionPolicy {
+        self.config
+            .rules
+            .iter()
+            .find(|rule| rule.range.matches(version))
+            .map(|rule| rule.policy)
+            .unwrap_or(self.config.default)
+    }
+
+    /// Looks up the policy for the version, turning away denied versions.
+    pub fn check(&self, version: Version) -> Result<(Version, VersionPolicy), ClientVersionError> {
+        match self.policy(&version) {
+            VersionPolicy::Denied => Err(ClientVersionError::Denied(version)),
+            policy => Ok((version, policy)),
+        }
+    }
+
+    /// Counts a connected client of the version until the returned guard is dropped.
+    pub fn track(&self, version: &Version) -> ConnectedClientVersion<'_> {
+        *self.connected.entry(version.clone()).or_default() += 1;
+
+        ConnectedClientVersion {
+            versions: self,
+            version: version.clone(),
+        }
+    }
+
+    /// Connected clients per version, newest version first.
+    pub fn distribution(&self) -> Vec<ClientVersionCount> {
+        let mut distribution = self
+            .connected
+            .iter()
+            .map(|entry| ClientVersionCount {
+                version: entry.key().clone(),
+                policy: self.policy(entry.key()),
+                connections: *entry.value(),
+            })
+            .collect::<Vec<_>>();
+
+        distribution.sort_by(|a, b| b.version.cmp(&a.version));
+        distribution
+    }
+}
+
+/// Connected client as counted by [ClientVersions::track].
+pub struct ConnectedClientVersion<'a> {
+    versions: &'a ClientVersions,
+    version: Version,
+}
+
+impl Drop for ConnectedClientVersion<'_> {
+    fn drop(&mut self) {
+        self.versions
+            .connected
+            .remove_if_mut(&self.version, |_, count| {
+                *count -= 1;
+                *count == 0
+            });
+    }
+}
+
+/// Reads the client version off of the upgrade request and checks it against the minimum
+/// version, if any.
+pub fn client_version(
+    request: &Request,
+    min_version: Option<&Version>,
+) -> Result<Version, ClientVersionError> {
+    let mut values = request.headers().get_all(CLIENT_VERSION_HEADER).iter();
+    let value = values.next().ok_or(ClientVersionError::Missing)?;
+    if values.next().is_some() {
+        return Err(ClientVersionError::Duplicate);
+    }
+
+    let value = value.to_str().map_err(|_| ClientVersionError::Malformed)?;
+    let version = Version::parse(value.trim().trim_start_matches('v'))
+        .map_err(|_| ClientVersionError::Malformed)?;
+
+    match min_version {
+        Some(min_version) if &version < min_version => {
+            Err(ClientVersionError::Outdated(version, min_version.clone()))
+        }
+        _ => Ok(version),
+    }
+}
+
+/// Response rejecting the websocket upgrade with the reason in the body.
+pub fn reject_upgrade(status: StatusCode, reason: impl ToString) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use semver::Version;
+    use tokio_tungstenite::tungstenite::{handshake::server::Request, http::StatusCode};
+
+    use super::{
+        client_version, ClientVersionConfig, ClientVersionError, ClientVersions, VersionPolicy,
+        CLIENT_VERSION_HEADER,
+    };
+
+    fn request(versions: &[&str]) -> Request {
+        let mut builder = Request::builder();
+        for version in versions {
+            builder = builder.header(CLIENT_VERSION_HEADER, *version);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn accepts_current_client() {
+        let min = Version::new(0, 3, 0);
+
+        assert_eq!(
+            client_version(&request(&["0.3.1"]), Some(&min)).unwrap(),
+            Version::new(0, 3, 1)
+        );
+        assert_eq!(
+            client_version(&request(&["v1.0.0"]), None).unwrap(),
+            Version::new(1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        assert!(matches!(
+            client_version(&request(&[]), None),
+            Err(ClientVersionError::Missing)
+        ));
+        assert!(matches!(
+            client_version(&request(&["0.3.1", "0.3.1"]), None),
+            Err(ClientVersionError::Duplicate)
+        ));
+        assert!(matches!(
+            client_version(&request(&["latest"]), None),
+            Err(ClientVersionError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn rejects_outdated_client() {
+        let error = client_version(&request(&["0.2.9"]), Some(&Version::new(0, 3, 0))).unwrap_err();
+
+        assert_eq!(error.status(), StatusCode::UPGRADE_REQUIRED);
+    }
+
+    #[test]
+    fn first_matching_rule_applies() {
+        let config: ClientVersionConfig = serde_yaml::from_str(
+            "
+            default: allowed
+            rules:
+              - range: '<0.2.0'
+                policy: denied
+              - range: '<0.3.0'
+                policy: degraded
+            ",
+        )
+        .unwrap();
+        let versions = ClientVersions::new(config);
+
+        assert_eq!(
+            versions.policy(&Version::new(0, 1, 5)),
+            VersionPolicy::Denied
+        );
+        assert_eq!(
+            versions.policy(&Version::new(0, 2, 1)),
+            VersionPolicy::Degraded
+        );
+        assert_eq!(
+            versions.policy(&Version::new(0, 3, 0)),
+            VersionPolicy::Allowed
+        );
+        assert!(matches!(
+            versions.check(Version::new(0, 1, 0)),
+            Err(ClientVersionError::Denied(_))
+        ));
+    }
+
+    #[test]
+    fn tracks_connected_versions() {
+        let versions = ClientVersions::default();
+        let older = Version::new(0, 2, 0);
+        let newer = Version::new(0, 3, 0);
+
+        let first = versions.track(&older);
+        let _second = versions.track(&older);
+        let _third = versions.track(&newer);
+
+        let distribution = versions.distribution();
+        assert_eq!(distribution[0].version, newer);
+        assert_eq!(distribution[1].connections, 2);
+
+        drop(first);
+        assert_eq!(versions.distribution()[1].connections, 1);
+    }
+
+    #[test]
+    fn parses_bundled_config() {
+        let _: ClientVersionConfig =
+            serde_yaml::from_str(include_str!("../../config/client_versions.yml")).unwrap();
+    }
+}
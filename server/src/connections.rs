@@ -0,0 +1,398 @@
+use std::{
+    collections::VecDeque,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use futures_util::future::join_all;
+use thiserror::Error;
+use tokio::{sync::oneshot, time::Instant};
+
+use crate::identity::{
+    authenticate, AuthenticatedSession, IdentityClaims, IdentityError, IdentityProvider,
+};
+
+#[derive(Debug, Error)]
+pub enum ConnectionRejection {
+    #[error("Server reached its connection limit of {0}.")]
+    ServerFull(usize),
+    #[error("Address reached its connection limit of {0}.")]
+    AddressLimit(usize),
+}
+
+/// Limits on concurrent connections. Omitted limits aren't enforced.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionLimits {
+    pub max_connections: Option<usize>,
+    pub max_per_address: Option<usize>,
+    /// Connections beyond this for the same external ID push out the oldest ones.
+    pub max_per_external_id: usize,
+}
+
+/// Keeps track of open connections to enforce the connection limits.
+pub struct ConnectionRegistry {
+    limits: ConnectionLimits,
+    total: AtomicUsize,
+    per_address: DashMap<IpAddr, usize>,
+    per_external_id: DashMap<u64, VecDeque<PlayerConnection>>,
+    next_id: AtomicU64,
+}
+
+struct PlayerConnection {
+    id: u64,
+    kick: oneshot::Sender<()>,
+    /// Resolves once the connection let go of its lease.
+    released: oneshot::Receiver<()>,
+}
+
+impl ConnectionRegistry {
+    pub fn new(limits: ConnectionLimits) -> Self {
+        Self {
+            limits,
+            total: Default::default(),
+            per_address: Default::default(),
+            per_external_id: Default::default(),
+            next_id: Default::default(),
+        }
+    }
+
+    /// Admits a freshly accepted connection if neither the server nor the peer's address are at
+    /// their limit. The connection counts against the limits until the permit is dropped.
+    pub fn admit(
+        self: &Arc<Self>,
+        address: IpAddr,
+    ) -> Result<ConnectionPermit, ConnectionRejection> {
+        if let Some(max) = self.limits.max_connections {
+            self.total
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                    (total < max).then_some(total + 1)
+                })
+                .map_err(|_| ConnectionRejection::ServerFull(max))?;
+        } else {
+            self.total.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut count = self.per_address.entry(address).or_default();
+        if self.limits.max_per_address.is_some_and(|max| *count >= max) {
+            drop(count);
+            self.release_address(address, false);
+            return Err(ConnectionRejection::AddressLimit(
+                self.limits.max_per_address.unwrap(),
+            ));
+        }
+        *count += 1;
+
+        Ok(ConnectionPermit {
+            registry: self.clone(),
+            address,
+        })
+    }
+
+    /// Authenticates the client with the identity backend and only then registers its connection,
+    /// so clients that can't prove to be the player can't push the player's connections out.
+    pub async fn authenticate_player(
+        self: &Arc<Self>,
+        identity: &dyn IdentityProvider,
+        claims: &IdentityClaims,
+        deadline: Instant,
+    ) -> Result<(PlayerLease, AuthenticatedSession), IdentityError> {
+        let session = authenticate(identity, claims, deadline).await?;
+
+        Ok((self.register_player(claims.external_id), session))
+    }
+
+    /// Registers an authenticated connection for the player. Kicks the player's oldest
+    /// connections if this one puts them over the limit, [PlayerLease::takeover] waits for them
+    /// to be gone.
+    pub fn register_player(self: &Arc<Self>, external_id: u64) -> PlayerLease {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (kick, kicked) = oneshot::channel();
+        let (release, released) = oneshot::channel();
+        let mut replaced = Vec::new();
+
+        let mut connections = self.per_external_id.entry(external_id).or_default();
+        while connections.len() >= self.limits.max_per_external_id.max(1) {
+            let Some(oldest) = connections.pop_front() else {
+                break;
+            };
+
+            log::info!(
+                external_id = external_id,
+                connection_id = oldest.id;
+                "Kicking older connection of player."
+            );
+            let _ = oldest.kick.send(());
+            replaced.push(oldest.released);
+        }
+        connections.push_back(PlayerConnection { id, kick, released });
+
+        PlayerLease {
+            registry: self.clone(),
+            external_id,
+            id,
+            kicked,
+            replaced,
+            _release: release,
+        }
+    }
+
+    pub fn connection_count(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Undoes the admission of a connection. The count for the address is only decremented for
+    /// connections that were counted against it.
+    fn release_address(&self, address: IpAddr, counted: bool) {
+        self.total.fetch_sub(1, Ordering::Relaxed);
+        self.per_address.remove_if_mut(&address, |_, count| {
+            if counted {
+                *count -= 1;
+            }
+            *count == 0
+        });
+    }
+}
+
+/// Admission of a connection. Frees up its slot when dropped.
+pub struct ConnectionPermit {
+    registry: Arc<ConnectionRegistry>,
+    address: IpAddr,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.registry.release_address(self.address, true);
+    }
+}
+
+/// Registration of a player's connection. Resolves `kicked` once a newer connection of the same
+/// player pushed this one out. Should outlive everything the connection holds on to in the
+/// services, as dropping it signals the connection is gone to the one that replaced it.
+pub struct PlayerLease {
+    registry: Arc<ConnectionRegistry>,
+    external_id: u64,
+    id: u64,
+    pub kicked: oneshot::Receiver<()>,
+    /// Connections kicked in favor of this one.
+    replaced: Vec<oneshot::Receiver<()>>,
+    _release: oneshot::Sender<()>,
+}
+
+impl PlayerLease {
+    /// Waits for the connections this one replaced to wind down, so they can't tear down pool
+    /// entries or the push channel of this one. Returns false if they didn't manage in time.
+    pub async fn takeover(&mut self, timeout: Duration) -> bool {
+        let replaced = std::mem::take(&mut self.replaced);
+        tokio::time::timeout(timeout, join_all(replaced))
+            .await
+            .is_ok()
+    }
+}
+
+impl Drop for PlayerLease {
+    fn drop(&mut self) {
+        self.registry
+            .per_external_id
+            .remove_if_mut(&self.external_id, |_, connections| {
+                connections.retain(|c| c.id != self.id);
+                connections.is_empty()
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        net::{IpAddr, Ipv4Addr},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use steamworks::{AuthSessionError, AuthSessionValidateError, SteamId};
+    use tokio::time::Instant;
+
+    use super::{ConnectionLimits, ConnectionRegistry};
+    use crate::{
+        identity::{IdentityClaims, IdentityError, IdentityProvider, IdentitySession},
+        steam::{AuthSessionBackend, AuthSessions},
+    };
+
+    const ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    fn registry(max_connections: usize, max_per_address: usize) -> Arc<ConnectionRegistry> {
+        Arc::new(ConnectionRegistry::new(ConnectionLimits {
+            max_connections: Some(max_connections),
+            max_per_address: Some(max_per_address),
+            max_per_external_id: 1,
+        }))
+    }
+
+    #[test]
+    fn enforces_address_limit() {
+        let registry = registry(10, 2);
+
+        let first = registry.admit(ADDRESS).unwrap();
+        let _second = registry.admit(ADDRESS).unwrap();
+        assert!(registry.admit(ADDRESS).is_err());
+        assert!(registry.admit(IpAddr::V4(Ipv4Addr::BROADCAST)).is_ok());
+
+        drop(first);
+        assert!(registry.admit(ADDRESS).is_ok());
+    }
+
+    #[test]
+    fn enforces_total_limit() {
+        let registry = registry(1, 10);
+
+        let first = registry.admit(ADDRESS).unwrap();
+        assert!(registry.admit(ADDRESS).is_err());
+        assert_eq!(registry.connection_count(), 1);
+
+        drop(first);
+        assert_eq!(registry.connection_count(), 0);
+        assert!(registry.per_address.is_empty());
+    }
+
+    #[test]
+    fn kicks_older_connection_of_player() {
+        let registry = registry(10, 10);
+
+        let mut first = registry.register_player(1);
+        let mut second = registry.register_player(1);
+
+        assert!(first.kicked.try_recv().is_ok());
+        assert!(second.kicked.try_recv().is_err());
+
+        drop(first);
+        drop(second);
+        assert!(registry.per_external_id.is_empty());
+    }
+
+    #[tokio::test]
+    async fn takeover_waits_for_replaced_connection() {
+        let registry = registry(10, 10);
+
+        let first = registry.register_player(1);
+        let mut second = registry.register_player(1);
+        assert!(!second.takeover(Duration::from_millis(10)).await);
+
+        let mut third = registry.register_player(1);
+        drop(first);
+        drop(second);
+        assert!(third.takeover(Duration::from_millis(10)).await);
+    }
+
+    /// Turns away every session, like Steam would for a forged ticket.
+    struct RejectingProvider;
+
+    impl IdentityProvider for RejectingProvider {
+        fn start_session(
+            &self,
+            claims: &IdentityClaims,
+        ) -> Result<Box<dyn IdentitySession>, IdentityError> {
+            Err(IdentityError::NotAllowed(claims.external_id))
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_session_keeps_existing_lease() {
+        let registry = registry(10, 10);
+        let mut existing = registry.register_player(1);
+
+        let impostor = IdentityClaims {
+            external_id: 1,
+            session_ticket: vec![],
+        };
+        assert!(registry
+            .authenticate_player(&RejectingProvider, &impostor, Instant::now())
+            .await
+            .is_err());
+
+        assert!(existing.kicked.try_recv().is_err());
+        assert_eq!(registry.per_external_id.get(&1).unwrap().len(), 1);
+    }
+
+    /// Holds a single auth session per steam ID like Steam does, keeping track of the ticket each
+    /// was begun with.
+    #[derive(Clone, Default)]
+    struct FakeSteam {
+        tickets: Arc<Mutex<HashMap<u64, Vec<u8>>>>,
+    }
+
+    impl AuthSessionBackend for FakeSteam {
+        fn begin_authentication_session(
+            &self,
+            steam_id: SteamId,
+            ticket: &[u8],
+        ) -> Result<(), AuthSessionError> {
+            let mut tickets = self.tickets.lock().unwrap();
+            if tickets.contains_key(&steam_id.raw()) {
+                return Err(AuthSessionError::DuplicateRequest);
+            }
+
+            tickets.insert(steam_id.raw(), ticket.to_vec());
+            Ok(())
+        }
+
+        fn end_authentication_session(&self, steam_id: SteamId) {
+            self.tickets.lock().unwrap().remove(&steam_id.raw());
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_ticket_keeps_existing_steam_session() {
+        let registry = registry(10, 10);
+        let backend = FakeSteam::default();
+        let steam = Arc::new(AuthSessions::new(backend.clone()));
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let claims = |ticket: &[u8]| IdentityClaims {
+            external_id: 1,
+            session_ticket: ticket.to_vec(),
+        };
+        let (genuine, forged) = (claims(b"genuine"), claims(b"forged"));
+
+        let (genuine, _) = tokio::join!(
+            registry.authenticate_player(&steam, &genuine, deadline),
+            async {
+                tokio::task::yield_now().await;
+                steam.report(1, Ok(()));
+            },
+        );
+        let (mut lease, mut genuine) = genuine.unwrap();
+
+        let (forged, _) = tokio::join!(
+            registry.authenticate_player(&steam, &forged, deadline),
+            async {
+                tokio::task::yield_now().await;
+                steam.report(
+                    1,
+                    Err(IdentityError::SteamValidation(
+                        AuthSessionValidateError::AuthTicketInvalid,
+                    )),
+                );
+            },
+        );
+        assert!(forged.is_err());
+        assert!(lease.kicked.try_recv().is_err());
+        assert_eq!(backend.tickets.lock().unwrap()[&1], b"genuine");
+
+        // Steam still reports on the genuine session.
+        steam.report(
+            1,
+            Err(IdentityError::SteamValidation(
+                AuthSessionValidateError::VACBanned,
+            )),
+        );
+        let validation = genuine.validation.as_mut().unwrap();
+        assert!(validation.try_recv().unwrap().is_err());
+
+        drop(genuine);
+        assert!(backend.tickets.lock().unwrap().is_empty());
+    }
+}
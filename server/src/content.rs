@@ -0,0 +1,338 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use message::eldenring::BloodMessageData;
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::{Pool, Postgres, QueryBuilder, Row};
+
+/// Kinds of user generated content players leave in the world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    BloodMessage,
+    Bloodstain,
+    GhostData,
+}
+
+impl ContentKind {
+    pub const ALL: [ContentKind; 3] = [
+        ContentKind::BloodMessage,
+        ContentKind::Bloodstain,
+        ContentKind::GhostData,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentKind::BloodMessage => "bloodmessage",
+            ContentKind::Bloodstain => "bloodstain",
+            ContentKind::GhostData => "ghostdata",
+        }
+    }
+
+    fn table(&self) -> &'static str {
+        match self {
+            ContentKind::BloodMessage => "bloodmessages",
+            ContentKind::Bloodstain => "bloodstains",
+            ContentKind::GhostData => "ghostdata",
+        }
+    }
+
+    fn id_column(&self) -> &'static str {
+        match self {
+            ContentKind::BloodMessage => "bloodmessage_id",
+            ContentKind::Bloodstain => "bloodstain_id",
+            ContentKind::GhostData => "ghostdata_id",
+        }
+    }
+
+    /// Column holding the payload moderators are interested in.
+    fn data_column(&self) -> &'static str {
+        match self {
+            ContentKind::BloodMessage => "data",
+            ContentKind::Bloodstain => "advertisement_data",
+            ContentKind::GhostData => "replay_data",
+        }
+    }
+
+    /// Only blood messages can be rated.
+    pub fn has_ratings(&self) -> bool {
+        matches!(self, ContentKind::BloodMessage)
+    }
+
+    /// Deletes the content matching the condition and writes each deleted item to the audit
+    /// log in the same statement. Binds the content type, moderator and reason as $2, $3 and $4.
+    fn audited_delete(&self, condition: &str) -> String {
+        format!(
+            "WITH deleted AS (
+                DELETE FROM {} WHERE {condition} RETURNING {} AS content_id, player_id
+            )
+            INSERT INTO content_deletions (content_type, content_id, player_id, moderator, reason)
+            SELECT $2, content_id, player_id, $3, $4 FROM deleted",
+            self.table(),
+            self.id_column()
+        )
+    }
+
+    /// Columns shared by all content, aliased so they map onto [ContentRecord].
+    fn summary_columns(&self) -> String {
+        let ratings = if self.has_ratings() {
+            "rating_good, rating_bad"
+        } else {
+            "NULL::int AS rating_good, NULL::int AS rating_bad"
+        };
+
+        format!(
+            "{} AS content_id, player_id, character_id,
+            (SELECT name FROM characters c WHERE c.character_id = {}.character_id) AS character_name,
+            area, play_region, created_at, shadowed, group_passwords, {ratings}",
+            self.id_column(),
+            self.table()
+        )
+    }
+}
+
+/// Filters for listing content. Leaving a filter out matches everything.
+#[derive(Debug, Default, Deserialize)]
+pub struct ContentFilter {
+    pub player_id: Option<i32>,
+    pub character_id: Option<i32>,
+    /// External ID as the players table knows it.
+    #[serde(skip)]
+    pub player_external_id: Option<String>,
+    pub play_region: Option<i32>,
+    pub area: Option<i32>,
+    /// Unix timestamps bounding the creation time.
+    pub created_after: Option<i64>,
+    pub created_before: Option<i64>,
+    /// Bounds on `rating_good - rating_bad`. Only applies to blood messages.
+    pub min_rating: Option<i32>,
+    pub max_rating: Option<i32>,
+}
+
+impl ContentFilter {
+    fn push_conditions(&self, kind: ContentKind, query: &mut QueryBuilder<'_, Postgres>) {
+        if let Some(player_id) = self.player_id {
+            query.push(" AND player_id = ").push_bind(player_id);
+        }
+        if let Some(character_id) = self.character_id {
+            query.push(" AND character_id = ").push_bind(character_id);
+        }
+        if let Some(external_id) = self.player_external_id.clone() {
+            query
+                .push(" AND player_id IN (SELECT player_id FROM players WHERE external_id = ")
+                .push_bind(external_id)
+                .push(")");
+        }
+        if let Some(play_region) = self.play_region {
+            query.push(" AND play_region = ").push_bind(play_region);
+        }
+        if let Some(area) = self.area {
+            query.push(" AND area = ").push_bind(area);
+        }
+        if let Some(created_after) = self.created_after {
+            query.push(" AND created_at >= ").push_bind(created_after);
+        }
+        if let Some(created_before) = self.created_before {
+            query.push(" AND created_at < ").push_bind(created_before);
+        }
+        if kind.has_ratings() {
+            if let Some(min_rating) = self.min_rating {
+                query
+                    .push(" AND rating_good - rating_bad >= ")
+                    .push_bind(min_rating);
+            }
+            if let Some(max_rating) = self.max_rating {
+                query
+                    .push(" AND rating_good - rating_bad <= ")
+                    .push_bind(max_rating);
+            }
+        }
+    }
+}
+
+pub struct ContentService {
+    pub database: Pool<Postgres>,
+}
+
+impl ContentService {
+    pub fn new(database: Pool<Postgres>) -> Self {
+        Self { database }
+    }
+
+    pub async fn list_content(
+        &self,
+        kind: ContentKind,
+        filter: &ContentFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ContentRecord>, sqlx::Error> {
+        let mut query = QueryBuilder::new(format!(
+            "SELECT {} FROM {} WHERE TRUE",
+            kind.summary_columns(),
+            kind.table()
+        ));
+        filter.push_conditions(kind, &mut query);
+        query
+            .push(format!(" ORDER BY {} DESC LIMIT ", kind.id_column()))
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        query
+            .build_query_as::<ContentRecord>()
+            .fetch_all(&self.database)
+            .await
+    }
+
+    pub async fn get_total(
+        &self,
+        kind: ContentKind,
+        filter: &ContentFilter,
+    ) -> Result<i64, sqlx::Error> {
+        let mut query =
+            QueryBuilder::new(format!("SELECT COUNT(*) FROM {} WHERE TRUE", kind.table()));
+        filter.push_conditions(kind, &mut query);
+
+        Ok(query.build().fetch_one(&self.database).await?.get(0))
+    }
+
+    pub async fn get_content(
+        &self,
+        kind: ContentKind,
+        content_id: i64,
+    ) -> Result<Option<ContentDetailRecord>, sqlx::Error> {
+        let mut content = sqlx::query_as::<_, ContentDetailRecord>(&format!(
+            "SELECT {}, {} AS data FROM {} WHERE {} = $1",
+            kind.summary_columns(),
+            kind.data_column(),
+            kind.table(),
+            kind.id_column()
+        ))
+        .bind(content_id)
+        .fetch_optional(&self.database)
+        .await?;
+
+        if let Some(content) = content
+            .as_mut()
+            .filter(|_| kind == ContentKind::BloodMessage)
+        {
+            content.decoded = wire::deserialize::<BloodMessageData>(&content.data).ok();
+        }
+
+        Ok(content)
+    }
+
+    /// Deletes a single piece of content. Returns false if it didn't exist.
+    pub async fn delete_content(
+        &self,
+        kind: ContentKind,
+        content_id: i64,
+        reason: Option<&str>,
+        moderator: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query(&kind.audited_delete(&format!("{} = $1", kind.id_column())))
+            .bind(content_id)
+            .bind(kind.as_str())
+            .bind(moderator)
+            .bind(reason)
+            .execute(&self.database)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    /// Deletes all content of the player with the given players table external ID, either all of
+    /// it or none. Returns how many items were removed of each kind.
+    pub async fn delete_player_content(
+        &self,
+        player_external_id: &str,
+        reason: Option<&str>,
+        moderator: &str,
+    ) -> Result<Vec<(ContentKind, u64)>, sqlx::Error> {
+        let mut transaction = self.database.begin().await?;
+
+        let mut deleted = Vec::new();
+        for kind in ContentKind::ALL {
+            let count = sqlx::query(&kind.audited_delete(
+                "player_id IN (SELECT player_id FROM players WHERE external_id = $1)",
+            ))
+            .bind(player_external_id)
+            .bind(kind.as_str())
+            .bind(moderator)
+            .bind(reason)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+            deleted.push((kind, count));
+        }
+
+        transaction.commit().await?;
+
+        Ok(deleted)
+    }
+
+    pub async fn list_deletions(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ContentDeletionRecord>, sqlx::Error> {
+        sqlx::query_as::<_, ContentDeletionRecord>(
+            "SELECT * FROM content_deletions ORDER BY content_deletion_id DESC LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.database)
+        .await
+    }
+
+    pub async fn get_deletions_total(&self) -> Result<i64, sqlx::Error> {
+        Ok(sqlx::query("SELECT COUNT(*) FROM content_deletions")
+            .fetch_one(&self.database)
+            .await?
+            .get(0))
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ContentRecord {
+    pub content_id: i64,
+    pub player_id: i32,
+    /// Character of the player that created the content, if known.
+    pub character_id: Option<i32>,
+    pub character_name: Option<String>,
+    pub area: i32,
+    pub play_region: i32,
+    pub created_at: i64,
+    pub shadowed: bool,
+    pub group_passwords: Vec<String>,
+    pub rating_good: Option<i32>,
+    pub rating_bad: Option<i32>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ContentDetailRecord {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub summary: ContentRecord,
+    #[serde(serialize_with = "serialize_base64")]
+    pub data: Vec<u8>,
+    /// Structured form of the data for blood messages. Absent if the data could not be decoded.
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<BloodMessageData>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ContentDeletionRecord {
+    pub content_deletion_id: i64,
+    pub content_type: String,
+    pub content_id: i64,
+    pub player_id: i32,
+    pub moderator: Option<String>,
+    pub reason: Option<String>,
+    pub created_at: i64,
+}
+
+fn serialize_base64<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&BASE64_STANDARD.encode(data))
+}
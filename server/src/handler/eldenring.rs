@@ -1,10 +1,15 @@
-use std::{collections::HashMap, fs::File, sync::mpsc::Sender};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    time::Instant,
+};
 
 use message::eldenring::{
     ObjectIdentifier, RequestGetAnnounceMessageListParams, RequestParams,
     ResponseGetAnnounceMessageListParams, ResponseGetAnnounceMessageListParamsEntry,
     ResponseParams, ResponsePollMatchingTicketParams,
 };
+use tokio::sync::mpsc::Sender;
 
 mod announcement;
 mod bloodmessage;
@@ -20,14 +25,17 @@ mod sign;
 mod visit;
 
 use crate::{
+    cheats::CheatRule,
     handler::eldenring::announcement::AnnouncementConfig,
     handler::{HandleRequest, RequestHandler},
     logging::LogContext,
     notification::NotificationChannelPoolToken,
     protocol::ClientSession,
+    restrictions::{PlayerRestrictions, Restriction},
     services::eldenring::{
-        breakin::BreakInPoolToken, quickmatch::QuickMatchPoolToken, sign::SignPoolToken,
-        visit::VisitorPoolToken, GameServices,
+        breakin::BreakInPoolToken, matchingticket::MatchingTicketToken,
+        quickmatch::QuickMatchPoolToken, sign::SignPoolToken, visit::VisitorPoolToken,
+        GameServices,
     },
 };
 
@@ -35,11 +43,23 @@ pub struct DefaultClientHandler<'a> {
     pub services: &'a GameServices,
     pub push_tx: Sender<Vec<u8>>,
     pub session: ClientSession,
+    pub restrictions: PlayerRestrictions,
+    /// Character the player is playing on, set at login and corrected by status updates.
+    pub character_id: Option<i32>,
+    /// Name the character was last resolved by.
+    pub character_name: Option<String>,
+    /// When the character's status was last stored.
+    pub last_snapshot_at: Option<Instant>,
+    /// Cheat rules the player was flagged for on this connection.
+    pub cheat_flags: HashSet<CheatRule>,
+    /// Set when the connection is to be closed after the current request.
+    pub disconnect: bool,
 
     pub sign_tokens: HashMap<ObjectIdentifier, SignPoolToken<'a>>,
     pub breakin_token: Option<BreakInPoolToken<'a>>,
     pub quickmatch_token: Option<QuickMatchPoolToken<'a>>,
     pub visitor_token: Option<VisitorPoolToken<'a>>,
+    pub matching_ticket_token: Option<MatchingTicketToken<'a>>,
 
     _notification_token: NotificationChannelPoolToken<'a>,
 }
@@ -49,6 +69,7 @@ impl<'a> DefaultClientHandler<'a> {
         services: &'a GameServices,
         push_tx: Sender<Vec<u8>>,
         session: ClientSession,
+        restrictions: PlayerRestrictions,
     ) -> Self {
         let _notification_token = services
             .notifications
@@ -58,10 +79,17 @@ impl<'a> DefaultClientHandler<'a> {
             services,
             push_tx,
             session,
+            restrictions,
+            character_id: None,
+            character_name: None,
+            last_snapshot_at: None,
+            cheat_flags: Default::default(),
+            disconnect: false,
             sign_tokens: Default::default(),
             breakin_token: Default::default(),
             quickmatch_token: Default::default(),
             visitor_token: Default::default(),
+            matching_ticket_token: Default::default(),
             _notification_token,
         }
     }
@@ -74,6 +102,36 @@ impl RequestHandler<RequestParams, ResponseParams> for DefaultClientHandler<'_>
     ) -> Result<Option<ResponseParams>, Box<dyn std::error::Error>> {
         LogContext::insert("player_id", self.session.player_id.to_string());
 
+        self.restrictions
+            .refresh(&self.services.restrictions)
+            .await?;
+        if let Some(restriction) = Restriction::for_request(request) {
+            if self.restrictions.contains(restriction) {
+                log::info!(
+                    context:serde = LogContext::current(),
+                    request_type = request.name(),
+                    restriction = restriction.as_str();
+                    "Refused request from restricted player."
+                );
+
+                return Ok(None);
+            }
+        }
+
+        if !self
+            .services
+            .rate_limiter
+            .check(&self.session, request.name())
+        {
+            log::warn!(
+                context:serde = LogContext::current(),
+                request_type = request.name();
+                "Refused request over rate limit."
+            );
+
+            return Ok(None);
+        }
+
         let result = match request {
             RequestParams::DeleteSession => ResponseParams::DeleteSession,
 
@@ -121,17 +179,21 @@ impl RequestHandler<RequestParams, ResponseParams> for DefaultClientHandler<'_>
                 ResponseParams::GetDeadingGhost(self.handle(request).await?)
             }
 
-            RequestParams::CreateBloodMessage(request) => {
-                ResponseParams::CreateBloodMessage(self.handle(request).await?)
-            }
+            RequestParams::CreateBloodMessage(request) => match self.handle(request).await? {
+                Some(response) => ResponseParams::CreateBloodMessage(response),
+                // Message was rejected by the blood message filter.
+                None => return Ok(None),
+            },
 
             RequestParams::GetBloodMessageList(request) => {
                 ResponseParams::GetBloodMessageList(self.handle(request).await?)
             }
 
-            RequestParams::EvaluateBloodMessage(request) => {
-                ResponseParams::EvaluateBloodMessage(self.handle(request).await?)
-            }
+            RequestParams::EvaluateBloodMessage(request) => match self.handle(request).await? {
+                Some(response) => ResponseParams::EvaluateBloodMessage(response),
+                // Players can't rate their own messages.
+                None => return Ok(None),
+            },
 
             RequestParams::RemoveBloodMessage(request) => {
                 ResponseParams::RemoveBloodMessage(self.handle(request).await?)
@@ -265,8 +327,23 @@ impl RequestHandler<RequestParams, ResponseParams> for DefaultClientHandler<'_>
     }
 }
 
-#[derive(Default)]
-pub struct BannedClientHandler {}
+/// Handler for clients that are not allowed to play. Only serves the announcements that tell them
+/// why.
+pub struct BannedClientHandler {
+    announcement_path: &'static str,
+}
+
+impl BannedClientHandler {
+    pub fn with_announcement(announcement_path: &'static str) -> Self {
+        Self { announcement_path }
+    }
+}
+
+impl Default for BannedClientHandler {
+    fn default() -> Self {
+        Self::with_announcement("config/ban_announcement.yml")
+    }
+}
 
 impl RequestHandler<RequestParams, ResponseParams> for BannedClientHandler {
     async fn dispatch_request(
@@ -309,9 +386,8 @@ impl HandleRequest<Box<RequestGetAnnounceMessageListParams>, ResponseGetAnnounce
         &mut self,
         _request: &Box<RequestGetAnnounceMessageListParams>,
     ) -> Result<ResponseGetAnnounceMessageListParams, Box<dyn std::error::Error>> {
-        let announcements = serde_yaml::from_reader::<_, AnnouncementConfig>(File::open(
-            "config/ban_announcement.yml",
-        )?)?;
+        let announcements =
+            serde_yaml::from_reader::<_, AnnouncementConfig>(File::open(self.announcement_path)?)?;
 
         Ok(ResponseGetAnnounceMessageListParams {
             changes: announcements
@@ -329,6 +405,6 @@ impl HandleRequest<Box<RequestGetAnnounceMessageListParams>, ResponseGetAnnounce
 }
 
 pub enum ActiveHandler<'a> {
-    Default(DefaultClientHandler<'a>),
+    Default(Box<DefaultClientHandler<'a>>),
     Banned(BannedClientHandler),
 }
@@ -1,11 +1,13 @@
+use std::sync::LazyLock;
+
 use rand::Rng;
 use sqlx::Row;
 
 use message::{
     builder::MessageBuilder,
     eldenring::{
-        BloodMessageRating, EvaluateBloodMessageParams, JoinParams, JoinPayload, ObjectIdentifier,
-        PlayRegionArea, PushParams, RequestCreateBloodMessageParams,
+        BloodMessageData, BloodMessageRating, EvaluateBloodMessageParams, JoinParams, JoinPayload,
+        ObjectIdentifier, PlayRegionArea, PushParams, RequestCreateBloodMessageParams,
         RequestEvaluateBloodMessageParams, RequestGetBloodMessageListParams,
         RequestReentryBloodMessageParams, RequestRemoveBloodMessageParams,
         ResponseCreateBloodMessageParams, ResponseEvaluateBloodMessageParams,
@@ -15,89 +17,266 @@ use message::{
 };
 
 use super::DefaultClientHandler;
-use crate::handler::HandleRequest;
+use crate::{
+    handler::HandleRequest,
+    logging::LogContext,
+    restrictions::Restriction,
+    services::eldenring::{
+        grouped_content_cte,
+        message_filter::{BloodMessageFilter, FilterAction},
+        message_selection::SelectionStrategy,
+    },
+};
+
+const FILTER_PATH: &str = "config/bloodmessage_filter.yml";
 
 const INSERT_QUERY: &str = "
     INSERT INTO bloodmessages (
         player_id,
         character_id,
+        client_character_id,
         session_id,
         rating_good,
         rating_bad,
         data,
         area,
         play_region,
-        group_passwords
+        group_passwords,
+        shadowed
     ) VALUES (
         $1,
         $2,
         $3,
+        $4,
         0,
         0,
-        $4,
         $5,
         $6,
-        $7
+        $7,
+        $8,
+        $9
     ) RETURNING bloodmessage_id";
 
-const SELECT_QUERY: &str = "
+static SELECT_QUERY: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "
     WITH pivot AS (SELECT random() AS r),
-         regions AS (SELECT unnest($1::int[]) AS region)
+         regions AS (SELECT unnest($1::int[]) AS region),
+         {grouped},
+         public AS (
+            SELECT bloodmessage_id FROM (
+                SELECT bloodmessage_id
+                FROM regions
+                CROSS JOIN LATERAL (
+                    SELECT bloodmessage_id, rnd
+                    FROM bloodmessages
+                    WHERE play_region = regions.region AND rnd >= (SELECT r FROM pivot)
+                        AND (NOT shadowed OR player_id = $2)
+                    ORDER BY rnd
+                    LIMIT 64
+                ) lat_above
+                ORDER BY rnd
+                LIMIT 64
+            ) above
+            UNION ALL
+            SELECT bloodmessage_id FROM (
+                SELECT bloodmessage_id
+                FROM regions
+                CROSS JOIN LATERAL (
+                    SELECT bloodmessage_id, rnd
+                    FROM bloodmessages
+                    WHERE play_region = regions.region AND rnd < (SELECT r FROM pivot)
+                        AND (NOT shadowed OR player_id = $2)
+                    ORDER BY rnd
+                    LIMIT 64
+                ) lat_below
+                ORDER BY rnd
+                LIMIT 64
+            ) below
+            LIMIT 64
+         )
     SELECT b.*
     FROM bloodmessages b
     JOIN (
-        SELECT bloodmessage_id FROM (
-            SELECT bloodmessage_id
-            FROM regions
-            CROSS JOIN LATERAL (
-                SELECT bloodmessage_id, rnd
-                FROM bloodmessages
-                WHERE play_region = regions.region AND rnd >= (SELECT r FROM pivot)
+        SELECT bloodmessage_id
+        FROM (
+            SELECT bloodmessage_id, 0 AS priority FROM grouped
+            UNION ALL
+            SELECT bloodmessage_id, 1 AS priority FROM public
+        ) picked
+        GROUP BY bloodmessage_id
+        ORDER BY MIN(priority)
+        LIMIT 64
+    ) s USING (bloodmessage_id)",
+        grouped = grouped_content_cte("bloodmessages", "bloodmessage_id")
+    )
+});
+
+/// Draws from a random sample several times larger than the response, picked through the same
+/// (play_region, rnd) index as [SELECT_QUERY]. Each candidate gets weighted by its rating, age and
+/// the ratings of its author, ratings being smoothed so new messages aren't buried. Sampling uses
+/// the Efraimidis-Spirakis key `-ln(u) / weight`.
+static SELECT_WEIGHTED_QUERY: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "
+    WITH pivot AS (SELECT random() AS r),
+         regions AS (SELECT unnest($1::int[]) AS region),
+         {grouped},
+         candidates AS (
+            SELECT bloodmessage_id FROM (
+                SELECT bloodmessage_id
+                FROM regions
+                CROSS JOIN LATERAL (
+                    SELECT bloodmessage_id, rnd
+                    FROM bloodmessages
+                    WHERE play_region = regions.region AND rnd >= (SELECT r FROM pivot)
+                        AND (NOT shadowed OR player_id = $2)
+                    ORDER BY rnd
+                    LIMIT 128
+                ) lat_above
                 ORDER BY rnd
-                LIMIT 64
-            ) lat_above
-            ORDER BY rnd
-            LIMIT 64
-        ) above
-        UNION ALL
-        SELECT bloodmessage_id FROM (
-            SELECT bloodmessage_id
-            FROM regions
-            CROSS JOIN LATERAL (
-                SELECT bloodmessage_id, rnd
-                FROM bloodmessages
-                WHERE play_region = regions.region AND rnd < (SELECT r FROM pivot)
+                LIMIT 128
+            ) above
+            UNION ALL
+            SELECT bloodmessage_id FROM (
+                SELECT bloodmessage_id
+                FROM regions
+                CROSS JOIN LATERAL (
+                    SELECT bloodmessage_id, rnd
+                    FROM bloodmessages
+                    WHERE play_region = regions.region AND rnd < (SELECT r FROM pivot)
+                        AND (NOT shadowed OR player_id = $2)
+                    ORDER BY rnd
+                    LIMIT 128
+                ) lat_below
                 ORDER BY rnd
-                LIMIT 64
-            ) lat_below
-            ORDER BY rnd
+                LIMIT 128
+            ) below
+         ),
+         sample AS (
+            SELECT b.* FROM candidates JOIN bloodmessages b USING (bloodmessage_id)
+         ),
+         public AS (
+            SELECT bloodmessage_id
+            FROM sample
+            LEFT JOIN players author ON author.player_id = sample.player_id
+            -- The weight is taken in log space and clamped as exp() raises an error rather than
+            -- returning 0 when it underflows.
+            ORDER BY -ln(1.0 - random()) / exp(GREATEST(
+                $5 * ln((sample.rating_good + 1)::float8 / (sample.rating_good + sample.rating_bad + 2))
+                - $6 * GREATEST(EXTRACT(EPOCH FROM NOW())::float8 - sample.created_at, 0) / 86400.0
+                + $7 * ln(
+                    (COALESCE(author.bloodmessage_rating_good, 0) + 1)::float8
+                    / (COALESCE(author.bloodmessage_rating_good, 0)
+                        + COALESCE(author.bloodmessage_rating_bad, 0) + 2)
+                ),
+                -700
+            ))
             LIMIT 64
-        ) below
+         )
+    SELECT b.*
+    FROM bloodmessages b
+    JOIN (
+        SELECT bloodmessage_id
+        FROM (
+            SELECT bloodmessage_id, 0 AS priority FROM grouped
+            UNION ALL
+            SELECT bloodmessage_id, 1 AS priority FROM public
+        ) picked
+        GROUP BY bloodmessage_id
+        ORDER BY MIN(priority)
         LIMIT 64
-    ) s USING (bloodmessage_id)";
+    ) s USING (bloodmessage_id)",
+        grouped = grouped_content_cte("bloodmessages", "bloodmessage_id")
+    )
+});
 
-impl HandleRequest<Box<RequestCreateBloodMessageParams>, ResponseCreateBloodMessageParams>
+/// Yields no response if the message was rejected by the blood message filter.
+impl HandleRequest<Box<RequestCreateBloodMessageParams>, Option<ResponseCreateBloodMessageParams>>
     for DefaultClientHandler<'_>
 {
     async fn handle(
         &mut self,
         request: &Box<RequestCreateBloodMessageParams>,
-    ) -> Result<ResponseCreateBloodMessageParams, Box<dyn std::error::Error>> {
+    ) -> Result<Option<ResponseCreateBloodMessageParams>, Box<dyn std::error::Error>> {
+        match wire::deserialize::<BloodMessageData>(&request.data) {
+            Ok(message) => {
+                log::info!(
+                    context:serde = LogContext::current(),
+                    template1 = message.template1,
+                    word1 = message.word1,
+                    conjunction = message.conjunction,
+                    template2 = message.template2,
+                    word2 = message.word2,
+                    gesture = message.gesture;
+                    "Creating blood message"
+                );
+            }
+            Err(e) => {
+                log::warn!(
+                    context:serde = LogContext::current(),
+                    error:% = e;
+                    "Could not decode blood message data"
+                );
+            }
+        }
+
+        let filter_action = check_filter(&request.data);
+
+        if let Some(action) = filter_action {
+            log::info!(
+                context:serde = LogContext::current(),
+                action = action.as_str();
+                "Blood message matched filter"
+            );
+        }
+
+        match filter_action {
+            Some(FilterAction::Reject) => return Ok(None),
+            // Hand out an identifier that doesn't exist so the client thinks all went well.
+            Some(FilterAction::Drop) => {
+                return Ok(Some(ResponseCreateBloodMessageParams {
+                    identifier: ObjectIdentifier(rand::rng().random::<i64>()),
+                }))
+            }
+            _ => {}
+        }
+        let shadowed = filter_action == Some(FilterAction::Shadow)
+            || self.restrictions.contains(Restriction::Shadow);
+
         let bloodmessage_id = sqlx::query(INSERT_QUERY)
             .bind(self.session.player_id)
+            .bind(self.character_id)
             .bind(request.character_id)
             .bind(self.session.session_id)
             .bind(&request.data)
             .bind(request.area.area as i32)
             .bind(request.area.play_region as i32)
             .bind(&request.group_passwords)
+            .bind(shadowed)
             .fetch_one(&self.services.database)
             .await?
             .get("bloodmessage_id");
 
-        Ok(ResponseCreateBloodMessageParams {
+        Ok(Some(ResponseCreateBloodMessageParams {
             identifier: ObjectIdentifier(bloodmessage_id),
-        })
+        }))
+    }
+}
+
+/// A filter that fails to load lets every message through rather than blocking all of them.
+fn check_filter(data: &[u8]) -> Option<FilterAction> {
+    match BloodMessageFilter::load(FILTER_PATH) {
+        Ok(filter) => filter.check_data(data),
+        Err(e) => {
+            log::warn!(
+                context:serde = LogContext::current(),
+                error:% = e;
+                "Could not load blood message filter"
+            );
+
+            None
+        }
     }
 }
 
@@ -114,14 +293,32 @@ impl HandleRequest<Box<RequestGetBloodMessageListParams>, ResponseGetBloodMessag
             .map(|a| a.play_region as i32)
             .collect::<Vec<i32>>();
 
-        let entries = sqlx::query_as::<_, BloodMessageRecord>(SELECT_QUERY)
-            .bind(play_regions)
+        let selection = &self.services.bloodmessage_selection;
+        let query = match selection.strategy {
+            SelectionStrategy::Uniform => sqlx::query_as::<_, BloodMessageRecord>(&SELECT_QUERY)
+                .bind(play_regions)
+                .bind(self.session.player_id)
+                .bind(&request.group_passwords)
+                .bind(self.services.group_content_limit(&request.group_passwords)),
+            SelectionStrategy::Weighted => {
+                sqlx::query_as::<_, BloodMessageRecord>(&SELECT_WEIGHTED_QUERY)
+                    .bind(play_regions)
+                    .bind(self.session.player_id)
+                    .bind(&request.group_passwords)
+                    .bind(self.services.group_content_limit(&request.group_passwords))
+                    .bind(selection.weights.rating)
+                    .bind(selection.weights.age)
+                    .bind(selection.weights.reputation)
+            }
+        };
+
+        let entries = query
             .fetch_all(&self.services.database)
             .await?
             .into_iter()
             .map(|e| ResponseGetBloodMessageListParamsEntry {
                 player_id: e.player_id,
-                character_id: e.character_id,
+                character_id: e.client_character_id,
                 identifier: ObjectIdentifier(e.bloodmessage_id),
                 rating_good: e.rating_good,
                 rating_bad: e.rating_bad,
@@ -138,28 +335,116 @@ impl HandleRequest<Box<RequestGetBloodMessageListParams>, ResponseGetBloodMessag
     }
 }
 
-impl HandleRequest<Box<RequestEvaluateBloodMessageParams>, ResponseEvaluateBloodMessageParams>
-    for DefaultClientHandler<'_>
+/// Yields no response if the player tried to rate their own message.
+impl
+    HandleRequest<
+        Box<RequestEvaluateBloodMessageParams>,
+        Option<ResponseEvaluateBloodMessageParams>,
+    > for DefaultClientHandler<'_>
 {
     async fn handle(
         &mut self,
         request: &Box<RequestEvaluateBloodMessageParams>,
-    ) -> Result<ResponseEvaluateBloodMessageParams, Box<dyn std::error::Error>> {
-        let rating = request.rating.try_into()?;
-        let query = match rating {
-            BloodMessageRating::Good => sqlx::query(
-                "UPDATE bloodmessages SET rating_good = rating_good + 1 WHERE bloodmessage_id = $1 RETURNING player_id",
-            ),
-            BloodMessageRating::Bad => sqlx::query(
-                "UPDATE bloodmessages SET rating_bad = rating_bad + 1 WHERE bloodmessage_id = $1 RETURNING player_id",
-            ),
-        };
+    ) -> Result<Option<ResponseEvaluateBloodMessageParams>, Box<dyn std::error::Error>> {
+        let rating: BloodMessageRating = request.rating.try_into()?;
 
-        let player_id: i32 = query
-            .bind(request.identifier.0)
-            .fetch_one(&self.services.database)
-            .await?
-            .get("player_id");
+        // Locking the message serializes evaluations of it so the counts can't drift.
+        let mut transaction = self.services.database.begin().await?;
+        let player_id: i32 = sqlx::query(
+            "SELECT player_id FROM bloodmessages WHERE bloodmessage_id = $1 FOR UPDATE",
+        )
+        .bind(request.identifier.0)
+        .fetch_one(&mut *transaction)
+        .await?
+        .get("player_id");
+
+        if player_id == self.session.player_id {
+            log::info!(
+                context:serde = LogContext::current(),
+                bloodmessage_id = request.identifier.0;
+                "Refused evaluation of own blood message"
+            );
+
+            return Ok(None);
+        }
+
+        let previous: Option<i32> = sqlx::query(
+            "SELECT rating FROM bloodmessage_evaluations WHERE bloodmessage_id = $1 AND player_id = $2",
+        )
+        .bind(request.identifier.0)
+        .bind(self.session.player_id)
+        .fetch_optional(&mut *transaction)
+        .await?
+        .map(|row| row.get("rating"));
+
+        match previous {
+            Some(previous) if previous == request.rating as i32 => {}
+            Some(previous) => {
+                sqlx::query(
+                    "UPDATE bloodmessage_evaluations
+                    SET rating = $3, updated_at = EXTRACT(EPOCH FROM NOW())
+                    WHERE bloodmessage_id = $1 AND player_id = $2",
+                )
+                .bind(request.identifier.0)
+                .bind(self.session.player_id)
+                .bind(request.rating as i32)
+                .execute(&mut *transaction)
+                .await?;
+
+                let previous: BloodMessageRating = (previous as u32).try_into()?;
+                sqlx::query(&format!(
+                    "UPDATE bloodmessages SET {0} = {0} - 1, {1} = {1} + 1 WHERE bloodmessage_id = $1",
+                    rating_column(&previous),
+                    rating_column(&rating),
+                ))
+                .bind(request.identifier.0)
+                .execute(&mut *transaction)
+                .await?;
+
+                sqlx::query(&format!(
+                    "UPDATE players SET bloodmessage_{0} = bloodmessage_{0} - 1,
+                        bloodmessage_{1} = bloodmessage_{1} + 1
+                    WHERE player_id = $1",
+                    rating_column(&previous),
+                    rating_column(&rating),
+                ))
+                .bind(player_id)
+                .execute(&mut *transaction)
+                .await?;
+            }
+            None => {
+                sqlx::query(
+                    "INSERT INTO bloodmessage_evaluations (bloodmessage_id, player_id, rating) VALUES ($1, $2, $3)",
+                )
+                .bind(request.identifier.0)
+                .bind(self.session.player_id)
+                .bind(request.rating as i32)
+                .execute(&mut *transaction)
+                .await?;
+
+                sqlx::query(&format!(
+                    "UPDATE bloodmessages SET {0} = {0} + 1 WHERE bloodmessage_id = $1",
+                    rating_column(&rating),
+                ))
+                .bind(request.identifier.0)
+                .execute(&mut *transaction)
+                .await?;
+
+                sqlx::query(&format!(
+                    "UPDATE players SET bloodmessage_{0} = bloodmessage_{0} + 1 WHERE player_id = $1",
+                    rating_column(&rating),
+                ))
+                .bind(player_id)
+                .execute(&mut *transaction)
+                .await?;
+            }
+        }
+        transaction.commit().await?;
+
+        // Only the first evaluation by a player is announced to the author.
+        if previous.is_some() {
+            return Ok(Some(ResponseEvaluateBloodMessageParams {}));
+        }
 
         let message = MessageBuilder::push()
             .body(PushParams::Join(JoinParams {
@@ -177,7 +462,14 @@ impl HandleRequest<Box<RequestEvaluateBloodMessageParams>, ResponseEvaluateBlood
             .notifications
             .notify_player(player_id, message)?;
 
-        Ok(ResponseEvaluateBloodMessageParams {})
+        Ok(Some(ResponseEvaluateBloodMessageParams {}))
+    }
+}
+
+fn rating_column(rating: &BloodMessageRating) -> &'static str {
+    match rating {
+        BloodMessageRating::Good => "rating_good",
+        BloodMessageRating::Bad => "rating_bad",
     }
 }
 
@@ -229,7 +521,7 @@ impl HandleRequest<Box<RequestReentryBloodMessageParams>, ResponseReentryBloodMe
 struct BloodMessageRecord {
     bloodmessage_id: i64,
     player_id: i32,
-    character_id: i32,
+    client_character_id: i32,
     rating_good: i32,
     rating_bad: i32,
     data: Vec<u8>,
@@ -1,3 +1,5 @@
+use std::sync::LazyLock;
+
 use message::eldenring::{
     ObjectIdentifier, PlayRegionArea, RequestCreateBloodstainParams,
     RequestGetBloodstainListParams, RequestGetDeadingGhostParams, ResponseCreateBloodstainParams,
@@ -6,7 +8,9 @@ use message::eldenring::{
 };
 use sqlx::Row;
 
-use crate::handler::HandleRequest;
+use crate::{
+    handler::HandleRequest, restrictions::Restriction, services::eldenring::grouped_content_cte,
+};
 
 use super::DefaultClientHandler;
 
@@ -18,7 +22,9 @@ const INSERT_QUERY: &str = "
         replay_data,
         area,
         play_region,
-        group_passwords
+        group_passwords,
+        shadowed,
+        character_id
     ) VALUES (
         $1,
         $2,
@@ -26,44 +32,65 @@ const INSERT_QUERY: &str = "
         $4,
         $5,
         $6,
-        $7
+        $7,
+        $8,
+        $9
     ) RETURNING bloodstain_id";
 
-const SELECT_QUERY: &str = "
+static SELECT_QUERY: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "
     WITH pivot AS (SELECT random() AS r),
-         regions AS (SELECT unnest($1::int[]) AS region)
-    SELECT b.*
-    FROM bloodstains b
-    JOIN (
-        SELECT bloodstain_id FROM (
-            SELECT bloodstain_id
-            FROM regions
-            CROSS JOIN LATERAL (
-                SELECT bloodstain_id, rnd
-                FROM bloodstains
-                WHERE play_region = regions.region AND rnd >= (SELECT r FROM pivot)
+         regions AS (SELECT unnest($1::int[]) AS region),
+         {grouped},
+         public AS (
+            SELECT bloodstain_id FROM (
+                SELECT bloodstain_id
+                FROM regions
+                CROSS JOIN LATERAL (
+                    SELECT bloodstain_id, rnd
+                    FROM bloodstains
+                    WHERE play_region = regions.region AND rnd >= (SELECT r FROM pivot)
+                        AND (NOT shadowed OR player_id = $2)
+                    ORDER BY rnd
+                    LIMIT 64
+                ) lat_above
                 ORDER BY rnd
                 LIMIT 64
-            ) lat_above
-            ORDER BY rnd
-            LIMIT 64
-        ) above
-        UNION ALL
-        SELECT bloodstain_id FROM (
-            SELECT bloodstain_id
-            FROM regions
-            CROSS JOIN LATERAL (
-                SELECT bloodstain_id, rnd
-                FROM bloodstains
-                WHERE play_region = regions.region AND rnd < (SELECT r FROM pivot)
+            ) above
+            UNION ALL
+            SELECT bloodstain_id FROM (
+                SELECT bloodstain_id
+                FROM regions
+                CROSS JOIN LATERAL (
+                    SELECT bloodstain_id, rnd
+                    FROM bloodstains
+                    WHERE play_region = regions.region AND rnd < (SELECT r FROM pivot)
+                        AND (NOT shadowed OR player_id = $2)
+                    ORDER BY rnd
+                    LIMIT 64
+                ) lat_below
                 ORDER BY rnd
                 LIMIT 64
-            ) lat_below
-            ORDER BY rnd
+            ) below
             LIMIT 64
-        ) below
+         )
+    SELECT b.*
+    FROM bloodstains b
+    JOIN (
+        SELECT bloodstain_id
+        FROM (
+            SELECT bloodstain_id, 0 AS priority FROM grouped
+            UNION ALL
+            SELECT bloodstain_id, 1 AS priority FROM public
+        ) picked
+        GROUP BY bloodstain_id
+        ORDER BY MIN(priority)
         LIMIT 64
-    ) s USING (bloodstain_id)";
+    ) s USING (bloodstain_id)",
+        grouped = grouped_content_cte("bloodstains", "bloodstain_id")
+    )
+});
 
 const SELECT_BY_ID_QUERY: &str = "
     SELECT *
@@ -85,6 +112,8 @@ impl HandleRequest<Box<RequestCreateBloodstainParams>, ResponseCreateBloodstainP
             .bind(request.area.area as i32)
             .bind(request.area.play_region as i32)
             .bind(&request.group_passwords)
+            .bind(self.restrictions.contains(Restriction::Shadow))
+            .bind(self.character_id)
             .fetch_one(&self.services.database)
             .await?
             .get("bloodstain_id");
@@ -109,8 +138,11 @@ impl HandleRequest<Box<RequestGetBloodstainListParams>, ResponseGetBloodstainLis
             .collect::<Vec<i32>>();
 
         let entries: Vec<ResponseGetBloodstainListParamsEntry> =
-            sqlx::query_as::<_, BloodstainRecord>(SELECT_QUERY)
+            sqlx::query_as::<_, BloodstainRecord>(&SELECT_QUERY)
                 .bind(play_regions)
+                .bind(self.session.player_id)
+                .bind(&request.group_passwords)
+                .bind(self.services.group_content_limit(&request.group_passwords))
                 .fetch_all(&self.services.database)
                 .await?
                 .into_iter()
@@ -91,7 +91,7 @@ impl HandleRequest<Box<RequestBreakInTargetParams>, ResponseBreakInTargetParams>
             });
         }
 
-        entry.target_tx.send(
+        entry.target_tx.try_send(
             MessageBuilder::push()
                 .body(PushParams::Join(JoinParams {
                     identifier: ObjectIdentifier(rand::rng().random::<i64>()),
@@ -123,7 +123,7 @@ impl HandleRequest<Box<RequestAllowBreakInTargetParams>, ResponseAllowBreakInTar
             .remove(&(pool_key, request.invading_player_id))
             .ok_or(Error::BreakInAttemptNotFound)?;
 
-        attempt.invader_tx.send(
+        attempt.invader_tx.try_send(
             MessageBuilder::push()
                 .body(PushParams::Join(JoinParams {
                     identifier: ObjectIdentifier(rand::rng().random::<i64>()),
@@ -153,7 +153,7 @@ impl HandleRequest<Box<RequestRejectBreakInTargetParams>, ResponseRejectBreakInT
             .remove(&(pool_key, request.invading_player_id))
             .ok_or(Error::BreakInAttemptNotFound)?;
 
-        attempt.invader_tx.send(
+        attempt.invader_tx.try_send(
             MessageBuilder::push()
                 .body(PushParams::Join(JoinParams {
                     identifier: ObjectIdentifier(rand::rng().random::<i64>()),
@@ -1,3 +1,5 @@
+use std::sync::LazyLock;
+
 use message::eldenring::{
     ObjectIdentifier, PlayRegionArea, RequestCreateGhostDataParams, RequestGetGhostDataListParams,
     ResponseCreateGhostDataParams, ResponseGetGhostDataListParams,
@@ -5,7 +7,9 @@ use message::eldenring::{
 };
 use sqlx::Row;
 
-use crate::handler::HandleRequest;
+use crate::{
+    handler::HandleRequest, restrictions::Restriction, services::eldenring::grouped_content_cte,
+};
 
 use super::DefaultClientHandler;
 
@@ -16,51 +20,74 @@ const INSERT_QUERY: &str = "
         replay_data,
         area,
         play_region,
-        group_passwords
+        group_passwords,
+        shadowed,
+        character_id
     ) VALUES (
         $1,
         $2,
         $3,
         $4,
         $5,
-        $6
+        $6,
+        $7,
+        $8
     ) RETURNING ghostdata_id";
 
-const SELECT_QUERY: &str = "
+static SELECT_QUERY: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "
     WITH pivot AS (SELECT random() AS r),
-         regions AS (SELECT unnest($1::int[]) AS region)
-    SELECT g.*
-    FROM ghostdata g
-    JOIN (
-        SELECT ghostdata_id FROM (
-            SELECT ghostdata_id
-            FROM regions
-            CROSS JOIN LATERAL (
-                SELECT ghostdata_id, rnd
-                FROM ghostdata
-                WHERE play_region = regions.region AND rnd >= (SELECT r FROM pivot)
+         regions AS (SELECT unnest($1::int[]) AS region),
+         {grouped},
+         public AS (
+            SELECT ghostdata_id FROM (
+                SELECT ghostdata_id
+                FROM regions
+                CROSS JOIN LATERAL (
+                    SELECT ghostdata_id, rnd
+                    FROM ghostdata
+                    WHERE play_region = regions.region AND rnd >= (SELECT r FROM pivot)
+                        AND (NOT shadowed OR player_id = $2)
+                    ORDER BY rnd
+                    LIMIT 64
+                ) lat_above
                 ORDER BY rnd
                 LIMIT 64
-            ) lat_above
-            ORDER BY rnd
-            LIMIT 64
-        ) above
-        UNION ALL
-        SELECT ghostdata_id FROM (
-            SELECT ghostdata_id
-            FROM regions
-            CROSS JOIN LATERAL (
-                SELECT ghostdata_id, rnd
-                FROM ghostdata
-                WHERE play_region = regions.region AND rnd < (SELECT r FROM pivot)
+            ) above
+            UNION ALL
+            SELECT ghostdata_id FROM (
+                SELECT ghostdata_id
+                FROM regions
+                CROSS JOIN LATERAL (
+                    SELECT ghostdata_id, rnd
+                    FROM ghostdata
+                    WHERE play_region = regions.region AND rnd < (SELECT r FROM pivot)
+                        AND (NOT shadowed OR player_id = $2)
+                    ORDER BY rnd
+                    LIMIT 64
+                ) lat_below
                 ORDER BY rnd
                 LIMIT 64
-            ) lat_below
-            ORDER BY rnd
+            ) below
             LIMIT 64
-        ) below
+         )
+    SELECT g.*
+    FROM ghostdata g
+    JOIN (
+        SELECT ghostdata_id
+        FROM (
+            SELECT ghostdata_id, 0 AS priority FROM grouped
+            UNION ALL
+            SELECT ghostdata_id, 1 AS priority FROM public
+        ) picked
+        GROUP BY ghostdata_id
+        ORDER BY MIN(priority)
         LIMIT 64
-    ) s USING (ghostdata_id)";
+    ) s USING (ghostdata_id)",
+        grouped = grouped_content_cte("ghostdata", "ghostdata_id")
+    )
+});
 
 impl HandleRequest<Box<RequestCreateGhostDataParams>, ResponseCreateGhostDataParams>
     for DefaultClientHandler<'_>
@@ -70,12 +97,14 @@ impl HandleRequest<Box<RequestCreateGhostDataParams>, ResponseCreateGhostDataPar
         request: &Box<RequestCreateGhostDataParams>,
     ) -> Result<ResponseCreateGhostDataParams, Box<dyn std::error::Error>> {
         let ghostdata_id = sqlx::query(INSERT_QUERY)
-            .bind(100)
-            .bind(100)
+            .bind(self.session.player_id)
+            .bind(self.session.session_id)
             .bind(&request.replay_data)
             .bind(request.area.area as i32)
             .bind(request.area.play_region as i32)
             .bind(&request.group_passwords)
+            .bind(self.restrictions.contains(Restriction::Shadow))
+            .bind(self.character_id)
             .fetch_one(&self.services.database)
             .await?
             .get("ghostdata_id");
@@ -100,8 +129,11 @@ impl HandleRequest<Box<RequestGetGhostDataListParams>, ResponseGetGhostDataListP
             .collect::<Vec<i32>>();
 
         let entries: Vec<ResponseGetGhostDataListParamsEntry> =
-            sqlx::query_as::<_, GhostDataRecord>(SELECT_QUERY)
+            sqlx::query_as::<_, GhostDataRecord>(&SELECT_QUERY)
                 .bind(play_regions)
+                .bind(self.session.player_id)
+                .bind(&request.group_passwords)
+                .bind(self.services.group_content_limit(&request.group_passwords))
                 .fetch_all(&self.services.database)
                 .await?
                 .into_iter()
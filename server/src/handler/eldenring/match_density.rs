@@ -1,6 +1,6 @@
 use message::eldenring::{RequestGetMatchDensityParams, ResponseGetMatchDensityParams};
 
-use crate::handler::HandleRequest;
+use crate::{handler::HandleRequest, services::eldenring::match_density::activity_bucket};
 
 use super::DefaultClientHandler;
 
@@ -11,19 +11,16 @@ impl HandleRequest<Box<RequestGetMatchDensityParams>, ResponseGetMatchDensityPar
         &mut self,
         _request: &Box<RequestGetMatchDensityParams>,
     ) -> Result<ResponseGetMatchDensityParams, Box<dyn std::error::Error>> {
-        // Some test stuff to fake match density
-        let areas = vec![
-            1100000, 1100001, 1100010, 1100011, 1100012, 1100013, 1100014, 1100015, 1100016,
-            1100017, 1100018, 1100019, 1100021, 1100091,
-        ];
+        let density = self.services.match_density();
 
-        let blue_activity = areas
-            .iter()
-            .enumerate()
-            .map(|(i, _)| (i % 9) as u8)
-            .collect();
-
-        let red_activity = areas.iter().map(|_| 3).collect();
+        let mut areas = Vec::with_capacity(density.regions.len());
+        let mut blue_activity = Vec::with_capacity(density.regions.len());
+        let mut red_activity = Vec::with_capacity(density.regions.len());
+        for (play_region, activity) in density.regions.iter() {
+            areas.push(*play_region as i32);
+            blue_activity.push(activity_bucket(activity.blue));
+            red_activity.push(activity_bucket(activity.red));
+        }
 
         Ok(ResponseGetMatchDensityParams {
             areas,
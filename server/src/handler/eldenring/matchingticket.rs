@@ -1,10 +1,18 @@
+use std::time::Instant;
+
 use message::eldenring::{
     RequestCreateMatchingTicketParams, RequestDeleteMatchingTicket,
     RequestPollMatchingTicketParams, ResponseCreateMatchingTicketParams,
     ResponseDeleteMatchingTicketParams, ResponsePollMatchingTicketParams,
 };
 
-use crate::handler::HandleRequest;
+use crate::{
+    handler::HandleRequest,
+    logging::LogContext,
+    services::eldenring::matchingticket::{
+        MatchingTicketEntry, MatchingTicketKey, MatchingTicketStatus,
+    },
+};
 
 use super::DefaultClientHandler;
 
@@ -15,7 +23,18 @@ impl HandleRequest<Box<RequestPollMatchingTicketParams>, ResponsePollMatchingTic
         &mut self,
         _request: &Box<RequestPollMatchingTicketParams>,
     ) -> Result<ResponsePollMatchingTicketParams, Box<dyn std::error::Error>> {
-        Ok(ResponsePollMatchingTicketParams { unk0: 0 })
+        let status = match self.matching_ticket_token.as_ref() {
+            Some(token) => self.services.pool_matching_ticket.poll(&token.1),
+            None => MatchingTicketStatus::Expired,
+        };
+
+        if status == MatchingTicketStatus::Expired {
+            let _ = self.matching_ticket_token.take();
+        }
+
+        Ok(ResponsePollMatchingTicketParams {
+            unk0: status as u32,
+        })
     }
 }
 
@@ -24,8 +43,40 @@ impl HandleRequest<Box<RequestCreateMatchingTicketParams>, ResponseCreateMatchin
 {
     async fn handle(
         &mut self,
-        _request: &Box<RequestCreateMatchingTicketParams>,
+        request: &Box<RequestCreateMatchingTicketParams>,
     ) -> Result<ResponseCreateMatchingTicketParams, Box<dyn std::error::Error>> {
+        // Tickets are keyed by player so the previous ticket has to be gone before the new one
+        // goes in, otherwise dropping its token would take the new ticket with it.
+        let _ = self.matching_ticket_token.take();
+
+        let pool = &self.services.pool_matching_ticket;
+        let token = pool.insert(
+            self.session.player_id,
+            MatchingTicketEntry {
+                player_id: self.session.player_id,
+                external_id: self.session.external_id.clone(),
+                unk1: request.unk1.clone(),
+                unk2: request.unk2,
+                created_at: Instant::now(),
+                matched_with: None,
+            },
+        );
+
+        if let Some(other) = pool
+            .get(&token.1)
+            .and_then(|e| e.matched_with)
+            .and_then(|other| pool.get(&MatchingTicketKey(other)))
+        {
+            log::info!(
+                context:serde = LogContext::current(),
+                other_player_id = other.player_id,
+                other_external_id = other.external_id;
+                "Matching ticket matched."
+            );
+        }
+
+        self.matching_ticket_token = Some(token);
+
         Ok(ResponseCreateMatchingTicketParams {})
     }
 }
@@ -37,6 +88,8 @@ impl HandleRequest<Box<RequestDeleteMatchingTicket>, ResponseDeleteMatchingTicke
         &mut self,
         _request: &Box<RequestDeleteMatchingTicket>,
     ) -> Result<ResponseDeleteMatchingTicketParams, Box<dyn std::error::Error>> {
+        let _ = self.matching_ticket_token.take();
+
         Ok(ResponseDeleteMatchingTicketParams {})
     }
 }
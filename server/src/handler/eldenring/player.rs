@@ -1,10 +1,15 @@
+use std::time::Instant;
+
 use message::eldenring::{
     RequestUpdateLoginPlayerCharacterParams, RequestUpdatePlayerStatusParams,
     ResponseUpdateLoginPlayerCharacterParams, ResponseUpdatePlayerStatusParams, VisitType,
 };
 
 use crate::{
+    cheats::{CheatAction, CHEAT_RULES_MODERATOR},
     handler::HandleRequest,
+    logging::LogContext,
+    protocol::parse_player_external_id,
     services::eldenring::{breakin::BreakInPoolEntry, visit::VisitorPoolEntry},
 };
 
@@ -20,8 +25,17 @@ impl
         &mut self,
         _request: &Box<RequestUpdateLoginPlayerCharacterParams>,
     ) -> Result<ResponseUpdateLoginPlayerCharacterParams, Box<dyn std::error::Error>> {
+        let character = self
+            .services
+            .characters
+            .login_character(self.session.player_id)
+            .await?;
+        self.character_id = Some(character.character_id);
+        self.character_name = character.name;
+        self.last_snapshot_at = None;
+
         Ok(ResponseUpdateLoginPlayerCharacterParams {
-            character_id: 0,
+            character_id: character.character_id as u32,
             unk1: 0,
             unk2: 0,
             unk3: 0,
@@ -39,6 +53,24 @@ impl HandleRequest<Box<RequestUpdatePlayerStatusParams>, ResponseUpdatePlayerSta
         &mut self,
         request: &Box<RequestUpdatePlayerStatusParams>,
     ) -> Result<ResponseUpdatePlayerStatusParams, Box<dyn std::error::Error>> {
+        let character_name = &request.character.character_name.0;
+        if !character_name.is_empty() && self.character_name.as_ref() != Some(character_name) {
+            self.character_id = Some(
+                self.services
+                    .characters
+                    .resolve_character(self.session.player_id, self.character_id, character_name)
+                    .await?,
+            );
+            self.character_name = Some(character_name.clone());
+            self.last_snapshot_at = None;
+        }
+
+        self.check_character(request).await?;
+        if self.disconnect {
+            // Keep the player out of the pools, they're on their way out anyway.
+            return Ok(ResponseUpdatePlayerStatusParams {});
+        }
+
         if request.character.multiplayer_data.can_be_hunter && self.visitor_token.is_none() {
             let token = self.services.pool_visitor.insert(
                 self.session.player_id,
@@ -96,6 +128,161 @@ impl HandleRequest<Box<RequestUpdatePlayerStatusParams>, ResponseUpdatePlayerSta
             _ => {}
         }
 
+        let snapshot_due = self
+            .last_snapshot_at
+            .is_none_or(|at| at.elapsed() >= self.services.characters.snapshot_interval);
+        if let Some(character_id) = self.character_id.filter(|_| snapshot_due) {
+            self.services
+                .characters
+                .record_snapshot(self.session.player_id, character_id, request)
+                .await?;
+            self.last_snapshot_at = Some(Instant::now());
+        }
+
         Ok(ResponseUpdatePlayerStatusParams {})
     }
 }
+
+impl DefaultClientHandler<'_> {
+    /// Runs the cheat rules over the reported character. Each broken rule is flagged once per
+    /// connection and its action taken.
+    async fn check_character(
+        &mut self,
+        request: &RequestUpdatePlayerStatusParams,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let external_id = parse_player_external_id(&self.session.external_id)?.to_string();
+
+        for violation in self.services.cheats.check(&request.character) {
+            if !self.cheat_flags.insert(violation.rule) {
+                continue;
+            }
+
+            log::warn!(
+                context:serde = LogContext::current(),
+                rule = violation.rule.as_str(),
+                action = violation.action.as_str(),
+                evidence = violation.evidence.as_str();
+                "Character broke cheat rule."
+            );
+
+            self.services
+                .cheats
+                .flag(
+                    &external_id,
+                    self.session.player_id,
+                    self.character_id,
+                    &violation,
+                )
+                .await?;
+
+            match violation.action {
+                CheatAction::Log => {}
+                CheatAction::Disconnect => self.disconnect = true,
+                CheatAction::Ban => {
+                    let reason = format!("{}: {}", violation.rule.as_str(), violation.evidence);
+                    self.services
+                        .bans
+                        .add_ban(&external_id, Some(&reason), None, CHEAT_RULES_MODERATOR)
+                        .await?;
+                    self.disconnect = true;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use message::eldenring::{
+        RequestUpdateLoginPlayerCharacterParams, RequestUpdatePlayerStatusParams,
+    };
+    use sqlx::{Pool, Postgres, Row};
+    use tokio::sync::mpsc::channel;
+    use wire::deserialize;
+
+    use crate::{
+        client_version::ClientVersions,
+        handler::{eldenring::DefaultClientHandler, HandleRequest},
+        identity::TrustedIdentityProvider,
+        protocol::ClientSession,
+        rate_limit::RateLimiter,
+        restrictions::PlayerRestrictions,
+        services::eldenring::{
+            message_selection::{BloodMessageSelection, SelectionStrategy, SelectionWeights},
+            GameServices,
+        },
+    };
+
+    fn services(database: Pool<Postgres>) -> GameServices {
+        GameServices::new(
+            database,
+            Box::new(TrustedIdentityProvider::new(&[])),
+            BloodMessageSelection {
+                strategy: SelectionStrategy::Uniform,
+                weights: SelectionWeights {
+                    rating: 0.0,
+                    age: 0.0,
+                    reputation: 0.0,
+                },
+            },
+            0.0,
+            RateLimiter::default(),
+            ClientVersions::default(),
+            std::time::Duration::ZERO,
+        )
+        .unwrap()
+    }
+
+    fn handler(services: &GameServices, player_id: i32) -> DefaultClientHandler<'_> {
+        let (push_tx, _) = channel(1);
+        let session = ClientSession {
+            player_id,
+            session_id: 1,
+            external_id: "110000100000001".to_string(),
+            peer_address: "127.0.0.1".to_string(),
+        };
+        let restrictions = PlayerRestrictions::new(session.external_id.clone());
+
+        DefaultClientHandler::new(services, push_tx, session, restrictions)
+    }
+
+    async fn login(handler: &mut DefaultClientHandler<'_>) -> u32 {
+        handler
+            .handle(&Box::new(RequestUpdateLoginPlayerCharacterParams {}))
+            .await
+            .unwrap()
+            .character_id
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database through DATABASE_URL"]
+    async fn login_character_is_stable(database: Pool<Postgres>) {
+        let player_id: i32 = sqlx::query(
+            "INSERT INTO players (external_id) VALUES ('110000100000001') RETURNING player_id",
+        )
+        .fetch_one(&database)
+        .await
+        .unwrap()
+        .get("player_id");
+        let services = services(database);
+
+        let mut first = handler(&services, player_id);
+        let character_id = login(&mut first).await;
+        assert_ne!(character_id, 0);
+
+        let status: RequestUpdatePlayerStatusParams = deserialize(include_bytes!(
+            "../../../../message/test/data/RequestUpdatePlayerStatus.bin"
+        ))
+        .unwrap();
+        first.handle(&Box::new(status)).await.unwrap();
+        assert_eq!(first.character_id, Some(character_id as i32));
+        drop(first);
+
+        let mut second = handler(&services, player_id);
+        assert_eq!(login(&mut second).await, character_id);
+        assert!(second.character_name.is_some());
+        assert_eq!(login(&mut second).await, character_id);
+    }
+}
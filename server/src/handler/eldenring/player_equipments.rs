@@ -22,7 +22,8 @@ const INSERT_QUERY: &str = "
     ) ON CONFLICT (pool, player_id) DO UPDATE SET
         data = EXCLUDED.data,
         session_id = EXCLUDED.session_id,
-        rnd = random()";
+        rnd = random(),
+        created_at = EXTRACT(EPOCH FROM NOW())";
 
 const SELECT_QUERY: &str = "
     WITH pivot AS (SELECT random() AS r)
@@ -159,7 +159,7 @@ impl HandleRequest<Box<RequestJoinQuickMatchParams>, ResponseJoinQuickMatchParam
             });
         }
 
-        entry.host_tx.send(
+        entry.host_tx.try_send(
             MessageBuilder::push()
                 .body(PushParams::Join(JoinParams {
                     identifier: ObjectIdentifier(rand::rng().random::<i64>()),
@@ -193,7 +193,7 @@ impl HandleRequest<Box<RequestAcceptQuickMatchParams>, ResponseAcceptQuickMatchP
             .remove(&(pool_key, request.joining_player_id))
             .ok_or(Error::QuickMatchJoinAttemptNotFound)?;
 
-        attempt.joining_player_tx.send(
+        attempt.joining_player_tx.try_send(
             MessageBuilder::push()
                 .body(PushParams::Join(JoinParams {
                     identifier: ObjectIdentifier(rand::rng().random::<i64>()),
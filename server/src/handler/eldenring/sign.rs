@@ -277,7 +277,7 @@ impl HandleRequest<Box<RequestSummonSignParams>, ResponseSummonSignParams>
             });
         }
 
-        entry.summonee_tx.send(
+        entry.summonee_tx.try_send(
             MessageBuilder::push()
                 .body(PushParams::Join(JoinParams {
                     identifier: ObjectIdentifier(rand::rng().random::<i64>()),
@@ -309,7 +309,7 @@ impl HandleRequest<Box<RequestRejectSignParams>, ResponseRejectSignParams>
             .remove(&(pool_key, request.summoning_player_id))
             .ok_or(Error::SummonAttemptNotFound)?;
 
-        attempt.summoner_tx.send(
+        attempt.summoner_tx.try_send(
             MessageBuilder::push()
                 .body(PushParams::Join(JoinParams {
                     identifier: ObjectIdentifier(rand::rng().random::<i64>()),
@@ -92,7 +92,7 @@ impl HandleRequest<Box<RequestVisitParams>, ResponseVisitParams> for DefaultClie
             });
         }
 
-        entry.visitor_tx.send(
+        entry.visitor_tx.try_send(
             MessageBuilder::push()
                 .body(PushParams::Join(JoinParams {
                     identifier: ObjectIdentifier(rand::rng().random::<i64>()),
@@ -126,7 +126,7 @@ impl HandleRequest<Box<RequestRejectVisitParams>, ResponseRejectVisitParams>
         //     .remove(&(pool_key, request.host_player_id))
         //     .ok_or(Error::VisitAttemptNotFound)?;
 
-        // attempt.summoner_tx.send(
+        // attempt.summoner_tx.try_send(
         //     MessageBuilder::push()
         //         .body(PushParams::Join(JoinParams {
         //             identifier: ObjectIdentifier(rand::rng().random::<i64>()),
@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+
+use clap::ValueEnum;
+use thiserror::Error;
+use tokio::{
+    sync::mpsc::UnboundedReceiver,
+    time::{timeout_at, Instant},
+};
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+
+#[derive(Debug, Error)]
+pub enum IdentityError {
+    #[error("Upgrade request missing {0} header.")]
+    MissingHeader(&'static str),
+    #[error("Upgrade request has malformed {0} header.")]
+    MalformedHeader(&'static str),
+    #[error("External ID {0} is not on the allowlist.")]
+    NotAllowed(u64),
+    #[error("Steam auth session error. {0}")]
+    Steam(#[from] steamworks::AuthSessionError),
+    #[error("Steam rejected auth ticket. {0:?}")]
+    SteamValidation(steamworks::AuthSessionValidateError),
+    #[error("Identity backend did not validate session in time.")]
+    ValidationTimeout,
+}
+
+/// Which identity backend the server authenticates clients against.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum IdentityProviderKind {
+    /// Validate session tickets against steamworks.
+    #[default]
+    Steam,
+    /// Trust the external ID the client sends, optionally restricted to an allowlist. Meant for
+    /// integration tests and LAN setups without Steam.
+    Trusted,
+}
+
+/// What to do with a connection once the identity backend rejects its session.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum IdentityRejectionAction {
+    /// Close the connection.
+    #[default]
+    Disconnect,
+    /// Keep the connection around but only serve it announcements.
+    Restrict,
+}
+
+/// Outcomes of the identity backend (re)validating a session after it has been started.
+pub type IdentityValidation = UnboundedReceiver<Result<(), IdentityError>>;
+
+/// Identity a client claims to have during the websocket upgrade.
+#[derive(Clone, Debug)]
+pub struct IdentityClaims {
+    pub external_id: u64,
+    pub session_ticket: Vec<u8>,
+}
+
+/// Handle to an authenticated session with the identity backend. Ends the session when dropped.
+pub trait IdentitySession: Send {
+    /// Takes the channel over which the backend reports validation outcomes for this session, if
+    /// the backend validates asynchronously at all.
+    fn validation(&mut self) -> Option<IdentityValidation> {
+        None
+    }
+}
+
+/// Session the identity backend accepted, along with the channel over which it reports later
+/// validation outcomes.
+pub struct AuthenticatedSession {
+    pub validation: Option<IdentityValidation>,
+    /// Held on to as dropping it ends the session.
+    _session: Box<dyn IdentitySession>,
+}
+
+/// Starts a session for the claims. Backends that validate asynchronously have to accept the
+/// session before the deadline, so a client can't pass as a player until it's confirmed to be
+/// them.
+pub async fn authenticate(
+    provider: &dyn IdentityProvider,
+    claims: &IdentityClaims,
+    deadline: Instant,
+) -> Result<AuthenticatedSession, IdentityError> {
+    let mut session = provider.start_session(claims)?;
+    let mut validation = session.validation();
+
+    if let Some(receiver) = validation.as_mut() {
+        match timeout_at(deadline, receiver.recv()).await {
+            Ok(Some(outcome)) => outcome?,
+            // Backend stopped reporting on the session, there's nothing left to wait for.
+            Ok(None) => validation = None,
+            Err(_) => return Err(IdentityError::ValidationTimeout),
+        }
+    }
+
+    Ok(AuthenticatedSession {
+        validation,
+        _session: session,
+    })
+}
+
+/// Backend that establishes who a connecting client is.
+pub trait IdentityProvider: Send + Sync {
+    /// Sample the identity claims off of the websocket upgrade request.
+    fn claims(&self, request: &Request) -> Result<IdentityClaims, IdentityError> {
+        let external_id = header(request, "x-steam-id")?
+            .parse::<u64>()
+            .map_err(|_| IdentityError::MalformedHeader("x-steam-id"))?;
+
+        let session_ticket = hex_to_bytes(header(request, "x-steam-session-ticket")?)
+            .ok_or(IdentityError::MalformedHeader("x-steam-session-ticket"))?;
+
+        Ok(IdentityClaims {
+            external_id,
+            session_ticket,
+        })
+    }
+
+    /// Authenticate the claims and start a session for the client.
+    fn start_session(
+        &self,
+        claims: &IdentityClaims,
+    ) -> Result<Box<dyn IdentitySession>, IdentityError>;
+}
+
+/// Accepts any client at its word. If an allowlist is configured only the external IDs on it will
+/// be let through.
+pub struct TrustedIdentityProvider {
+    allowlist: Option<HashSet<u64>>,
+}
+
+impl TrustedIdentityProvider {
+    pub fn new(allowlist: &[u64]) -> Self {
+        Self {
+            allowlist: (!allowlist.is_empty()).then(|| allowlist.iter().copied().collect()),
+        }
+    }
+}
+
+impl IdentityProvider for TrustedIdentityProvider {
+    fn claims(&self, request: &Request) -> Result<IdentityClaims, IdentityError> {
+        let external_id = header(request, "x-steam-id")?
+            .parse::<u64>()
+            .map_err(|_| IdentityError::MalformedHeader("x-steam-id"))?;
+
+        // We're not going to validate the ticket anyways so don't hold it against the client if
+        // it didn't send one.
+        let session_ticket = request
+            .headers()
+            .get("x-steam-session-ticket")
+            .and_then(|v| v.to_str().ok())
+            .and_then(hex_to_bytes)
+            .unwrap_or_default();
+
+        Ok(IdentityClaims {
+            external_id,
+            session_ticket,
+        })
+    }
+
+    fn start_session(
+        &self,
+        claims: &IdentityClaims,
+    ) -> Result<Box<dyn IdentitySession>, IdentityError> {
+        if let Some(allowlist) = self.allowlist.as_ref() {
+            if !allowlist.contains(&claims.external_id) {
+                return Err(IdentityError::NotAllowed(claims.external_id));
+            }
+        }
+
+        Ok(Box::new(TrustedSession))
+    }
+}
+
+pub struct TrustedSession;
+
+impl IdentitySession for TrustedSession {}
+
+/// Reads a header that has to be sent exactly once.
+fn header<'a>(request: &'a Request, name: &'static str) -> Result<&'a str, IdentityError> {
+    let mut values = request.headers().get_all(name).iter();
+    let value = values.next().ok_or(IdentityError::MissingHeader(name))?;
+    if values.next().is_some() {
+        return Err(IdentityError::MalformedHeader(name));
+    }
+
+    value
+        .to_str()
+        .map_err(|_| IdentityError::MalformedHeader(name))
+}
+
+fn hex_to_bytes(s: &str) -> Option<Vec<u8>> {
+    if s.len().is_multiple_of(2) {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| {
+                s.get(i..i + 2)
+                    .and_then(|sub| u8::from_str_radix(sub, 16).ok())
+            })
+            .collect()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio_tungstenite::tungstenite::handshake::server::Request;
+
+    use super::{IdentityClaims, IdentityProvider, TrustedIdentityProvider};
+
+    fn upgrade_request(steam_id: &str, ticket: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri("/").header("x-steam-id", steam_id);
+        if let Some(ticket) = ticket {
+            builder = builder.header("x-steam-session-ticket", ticket);
+        }
+
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn trusted_parses_claims_without_ticket() {
+        let provider = TrustedIdentityProvider::new(&[]);
+        let claims = provider
+            .claims(&upgrade_request("76561197960287930", None))
+            .unwrap();
+
+        assert_eq!(claims.external_id, 76561197960287930);
+        assert!(claims.session_ticket.is_empty());
+    }
+
+    #[test]
+    fn trusted_parses_ticket() {
+        let provider = TrustedIdentityProvider::new(&[]);
+        let claims = provider
+            .claims(&upgrade_request("1", Some("00ff10")))
+            .unwrap();
+
+        assert_eq!(claims.session_ticket, vec![0x00, 0xff, 0x10]);
+    }
+
+    #[test]
+    fn trusted_rejects_malformed_id() {
+        let provider = TrustedIdentityProvider::new(&[]);
+
+        assert!(provider.claims(&upgrade_request("abc", None)).is_err());
+    }
+
+    #[test]
+    fn trusted_without_allowlist_accepts_anyone() {
+        let provider = TrustedIdentityProvider::new(&[]);
+        let claims = IdentityClaims {
+            external_id: 1,
+            session_ticket: vec![],
+        };
+
+        assert!(provider.start_session(&claims).is_ok());
+    }
+
+    #[test]
+    fn trusted_allowlist_is_enforced() {
+        let provider = TrustedIdentityProvider::new(&[1]);
+        let allowed = IdentityClaims {
+            external_id: 1,
+            session_ticket: vec![],
+        };
+        let denied = IdentityClaims {
+            external_id: 2,
+            session_ticket: vec![],
+        };
+
+        assert!(provider.start_session(&allowed).is_ok());
+        assert!(provider.start_session(&denied).is_err());
+    }
+}
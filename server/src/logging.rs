@@ -31,6 +31,15 @@ impl LogContext {
         LOG_CONTEXT.scope(LogContext::new(), f)
     }
 
+    /// Runs the future with a copy of the current context. Used to carry the context over to
+    /// tasks spawned on behalf of the current one.
+    pub fn inherit<F>(f: F) -> TaskLocalFuture<LogContext, F>
+    where
+        F: Future,
+    {
+        LOG_CONTEXT.scope(LogContext::current(), f)
+    }
+
     pub fn insert(key: impl Into<String>, value: impl Into<String>) {
         LOG_CONTEXT.with(|ctx| {
             ctx.context.borrow_mut().insert(key.into(), value.into());
@@ -1,42 +1,88 @@
 use std::{
+    collections::HashMap,
     error::Error,
     net::SocketAddr,
-    sync::{mpsc::channel, Arc, OnceLock},
-    time::UNIX_EPOCH,
+    sync::{Arc, OnceLock},
+    time::{Duration, UNIX_EPOCH},
 };
 
 use actix_web::{web, App, HttpServer};
 use api::{
-    auth::CheckKey,
-    ban::{delete_ban, get_ban, get_ban_by_id, post_ban},
+    auth::{CheckKey, LabeledApiKey, DEFAULT_API_KEY_LABEL},
+    ban::{delete_ban, get_ban, get_ban_by_id, get_ban_events, post_ban},
+    character::{get_character, get_character_snapshots, get_player_characters},
+    cheat::{get_cheat_flags, get_cheat_flags_by_id},
+    client_version::get_client_versions,
+    content::{
+        delete_content, delete_player_content, get_content, get_content_by_id,
+        get_content_deletions,
+    },
     health::healthcheck,
     notification::announcement,
+    rate_limit::get_rate_limit_offenders,
+    restriction::{delete_restriction, get_restrictions, get_restrictions_by_id, post_restriction},
+    retention::get_retention,
+    shutdown::post_shutdown,
     AppState,
 };
-use clap::Parser;
-use futures_util::{SinkExt, StreamExt};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use futures_util::{stream::SplitSink, SinkExt, StreamExt};
 use handler::{
     eldenring::{ActiveHandler, BannedClientHandler, DefaultClientHandler},
     RequestHandler,
 };
 use message::{builder::MessageBuilder, reader::MessageReader, MessageType};
 use protocol::ClientProtocol;
-use services::eldenring::GameServices;
+use services::eldenring::{
+    message_selection::{parse_weight, BloodMessageSelection, SelectionStrategy, SelectionWeights},
+    GameServices,
+};
 use sqlx::{Pool, Postgres};
 use thiserror::Error;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{channel, Receiver},
+        watch, Mutex,
+    },
+    task::JoinHandle,
+    time::{timeout_at, Instant, MissedTickBehavior},
+};
 use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
-use tokio_tungstenite::tungstenite::Message;
-
-use crate::logging::LogContext;
+use tokio_tungstenite::tungstenite::{http::StatusCode, Message};
+use tokio_tungstenite::WebSocketStream;
+
+use crate::{
+    client_version::{client_version, reject_upgrade, ClientVersions, VersionPolicy},
+    connections::{ConnectionLimits, ConnectionRegistry},
+    identity::{
+        IdentityError, IdentityProvider, IdentityProviderKind, IdentityRejectionAction,
+        IdentityValidation, TrustedIdentityProvider,
+    },
+    logging::LogContext,
+    rate_limit::RateLimiter,
+    restrictions::PlayerRestrictions,
+    shutdown::{ShutdownCoordinator, ShutdownPhase},
+    steam::SteamServer,
+};
 
 mod api;
 mod bans;
+mod characters;
+mod cheats;
+mod client_version;
+mod connections;
+mod content;
 mod handler;
+mod identity;
 mod logging;
 mod notification;
 mod protocol;
+mod rate_limit;
+mod restrictions;
+mod retention;
 mod services;
+mod shutdown;
 mod steam;
 
 #[derive(Parser, Debug)]
@@ -55,6 +101,11 @@ struct Config {
     #[arg(long, env("WAYGATE_API_KEY"))]
     api_key: String,
 
+    /// Additional comma-separated API keys formatted as `label=key`. The label is recorded
+    /// alongside moderation actions taken with the key.
+    #[arg(long, env("WAYGATE_API_KEYS"), value_delimiter = ',')]
+    api_keys: Vec<LabeledApiKey>,
+
     /// Database URL pointing to the postgresql instance.
     #[arg(long, env("WAYGATE_DATABASE"))]
     database: String,
@@ -68,11 +119,175 @@ struct Config {
     /// This key should be kept secret.
     #[arg(long, env("WAYGATE_SERVER_SECRET_KEY"))]
     server_secret_key: String,
+
+    /// Backend used to establish the identity of connecting clients.
+    #[arg(long, env("WAYGATE_IDENTITY_PROVIDER"), value_enum, default_value_t)]
+    identity_provider: IdentityProviderKind,
+
+    /// Comma-separated steam IDs allowed to connect when using the trusted identity provider.
+    /// Leaving this empty lets anyone in.
+    #[arg(long, env("WAYGATE_IDENTITY_ALLOWLIST"), value_delimiter = ',')]
+    identity_allowlist: Vec<u64>,
+
+    /// What to do with a client whose session gets rejected by the identity backend after it
+    /// connected.
+    #[arg(
+        long,
+        env("WAYGATE_IDENTITY_REJECTION_ACTION"),
+        value_enum,
+        default_value_t
+    )]
+    identity_rejection_action: IdentityRejectionAction,
+
+    /// Seconds connected clients get to leave by themselves when the server is shutting down.
+    #[arg(long, env("WAYGATE_SHUTDOWN_GRACE_PERIOD"), default_value_t = 300)]
+    shutdown_grace_period: u64,
+
+    /// Announcement broadcast to connected clients when the server is shutting down. `{minutes}`
+    /// is replaced by the grace period in minutes.
+    #[arg(
+        long,
+        env("WAYGATE_SHUTDOWN_MESSAGE"),
+        default_value = "Server restarting in {minutes} minutes."
+    )]
+    shutdown_message: String,
+
+    /// Amount of outbound messages that may be queued up for a single client. Pushes to a client
+    /// whose queue is full are rejected instead of piling up in memory.
+    #[arg(long, env("WAYGATE_PUSH_CHANNEL_CAPACITY"), default_value_t = 64)]
+    push_channel_capacity: usize,
+
+    /// How blood messages are picked when a client asks for the messages around it.
+    #[arg(
+        long,
+        env("WAYGATE_BLOODMESSAGE_SELECTION"),
+        value_enum,
+        default_value_t
+    )]
+    bloodmessage_selection: SelectionStrategy,
+
+    /// Exponent applied to the share of positive ratings of a message when using weighted
+    /// selection.
+    #[arg(
+        long,
+        env("WAYGATE_BLOODMESSAGE_RATING_WEIGHT"),
+        value_parser = parse_weight,
+        default_value_t = 1.0
+    )]
+    bloodmessage_rating_weight: f64,
+
+    /// Exponential decay per day of age of a message when using weighted selection.
+    #[arg(
+        long,
+        env("WAYGATE_BLOODMESSAGE_AGE_WEIGHT"),
+        value_parser = parse_weight,
+        default_value_t = 0.02
+    )]
+    bloodmessage_age_weight: f64,
+
+    /// Exponent applied to the share of positive ratings across all messages of the author when
+    /// using weighted selection.
+    #[arg(
+        long,
+        env("WAYGATE_BLOODMESSAGE_REPUTATION_WEIGHT"),
+        value_parser = parse_weight,
+        default_value_t = 0.5
+    )]
+    bloodmessage_reputation_weight: f64,
+
+    /// Share, between 0 and 1, of the blood messages, bloodstains and ghosts served to a player
+    /// that is reserved for content sharing one of their group passwords. Remaining slots are
+    /// filled with public content.
+    #[arg(long, env("WAYGATE_GROUP_CONTENT_SHARE"), default_value_t = 0.5)]
+    group_content_share: f64,
+
+    /// Seconds between pruning runs of player content. Limits are set in `config/retention.yml`.
+    #[arg(long, env("WAYGATE_RETENTION_INTERVAL"), default_value_t = 3600)]
+    retention_interval: u64,
+
+    /// Maximum amount of concurrent game connections. Unlimited if omitted.
+    #[arg(long, env("WAYGATE_MAX_CONNECTIONS"))]
+    max_connections: Option<usize>,
+
+    /// Maximum amount of concurrent game connections from a single IP address. Unlimited if
+    /// omitted.
+    #[arg(long, env("WAYGATE_MAX_CONNECTIONS_PER_ADDRESS"))]
+    max_connections_per_address: Option<usize>,
+
+    /// Maximum amount of concurrent game connections per player. Once reached, a new connection
+    /// of the player disconnects their oldest one.
+    #[arg(long, env("WAYGATE_MAX_CONNECTIONS_PER_PLAYER"), default_value_t = 1)]
+    max_connections_per_player: usize,
+
+    /// Seconds a client gets to complete the websocket upgrade and key exchange.
+    #[arg(long, env("WAYGATE_HANDSHAKE_TIMEOUT"), default_value_t = 10)]
+    handshake_timeout: u64,
+
+    /// Seconds a client may go without sending anything before it's considered dead and
+    /// disconnected. Has to be longer than the ping interval so pongs can come in in time.
+    #[arg(
+        long,
+        env("WAYGATE_IDLE_TIMEOUT"),
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value_t = 120
+    )]
+    idle_timeout: u64,
+
+    /// Seconds between websocket pings sent to clients. Their pongs keep connections of clients
+    /// that are quiet but alive from timing out.
+    #[arg(
+        long,
+        env("WAYGATE_PING_INTERVAL"),
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value_t = 30
+    )]
+    ping_interval: u64,
+
+    /// Oldest Waygate client version allowed to connect. Older clients are turned away with a
+    /// 426 Upgrade Required during the websocket upgrade.
+    #[arg(long, env("WAYGATE_MIN_CLIENT_VERSION"))]
+    min_client_version: Option<semver::Version>,
+
+    /// Minimum seconds between two stored snapshots of a character's status.
+    #[arg(long, env("WAYGATE_STATUS_SNAPSHOT_INTERVAL"), default_value_t = 300)]
+    status_snapshot_interval: u64,
 }
 
+/// Announcement served to clients restricted after their session got rejected.
+const IDENTITY_REJECTION_ANNOUNCEMENT: &str = "config/identity_announcement.yml";
+
+/// Time given to an earlier connection of a player to close when they connect again.
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time given to the writer to get queued messages out before a connection is closed by the
+/// server.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Per player budgets for request types.
+const RATE_LIMITS: &str = "config/rate_limits.yml";
+
+/// Policies for Waygate client versions.
+const CLIENT_VERSIONS: &str = "config/client_versions.yml";
+
+/// Announcement served to clients running a degraded Waygate version.
+const UPDATE_ANNOUNCEMENT: &str = "config/update_announcement.yml";
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let config = Config::parse();
+    if config.idle_timeout <= config.ping_interval {
+        Config::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--idle-timeout ({}) has to be longer than --ping-interval ({}), or quiet \
+                    clients would be disconnected before their pong comes in",
+                    config.idle_timeout, config.ping_interval
+                ),
+            )
+            .exit();
+    }
+
     log4rs::init_file("config/logging.yml", Default::default()).unwrap();
     log::info!("Bootstrapping with config {config:#?}");
 
@@ -82,17 +297,77 @@ async fn main() -> Result<(), Box<dyn Error>> {
     sqlx::migrate!("./migrations").run(&database).await?;
     log::info!("Initialized database");
 
-    let services = Arc::new(GameServices::new(database.clone())?);
+    let identity: Box<dyn IdentityProvider> = match config.identity_provider {
+        IdentityProviderKind::Steam => Box::new(SteamServer::init()?),
+        IdentityProviderKind::Trusted => {
+            log::warn!("Using trusted identity provider, client identities will not be validated");
+            Box::new(TrustedIdentityProvider::new(&config.identity_allowlist))
+        }
+    };
 
-    tokio::select! {
-        _ = serve_websockets(config.clone(), database.clone(), services.clone()) => {
-            log::info!("Websocket server stopped listening");
-        },
-        _ = serve_api(config.clone(), database.clone(), services.clone()) => {
-            log::info!("API server stopped listening");
+    let bloodmessage_selection = BloodMessageSelection {
+        strategy: config.bloodmessage_selection,
+        weights: SelectionWeights {
+            rating: config.bloodmessage_rating_weight,
+            age: config.bloodmessage_age_weight,
+            reputation: config.bloodmessage_reputation_weight,
         },
     };
+    let rate_limiter = RateLimiter::load(RATE_LIMITS).unwrap_or_else(|e| {
+        log::warn!("Could not load rate limits, requests won't be rate limited: {e}");
+        RateLimiter::default()
+    });
+    let client_versions = ClientVersions::load(CLIENT_VERSIONS).unwrap_or_else(|e| {
+        log::warn!("Could not load client version policies, all versions are allowed: {e}");
+        ClientVersions::default()
+    });
+    let services = Arc::new(GameServices::new(
+        database.clone(),
+        identity,
+        bloodmessage_selection,
+        config.group_content_share,
+        rate_limiter,
+        client_versions,
+        Duration::from_secs(config.status_snapshot_interval),
+    )?);
+
+    tokio::spawn(protocol::sweep_expired_sessions(database.clone()));
+    tokio::spawn(rate_limit::sweep_rate_limit_buckets(services.clone()));
+    tokio::spawn(retention::prune_periodically(
+        services.clone(),
+        Duration::from_secs(config.retention_interval),
+    ));
+
+    let shutdown = Arc::new(ShutdownCoordinator::new(
+        services.clone(),
+        Duration::from_secs(config.shutdown_grace_period),
+        config.shutdown_message.clone(),
+    ));
+    tokio::spawn(shutdown.clone().listen_for_signals());
+
+    let connections = Arc::new(ConnectionRegistry::new(ConnectionLimits {
+        max_connections: config.max_connections,
+        max_per_address: config.max_connections_per_address,
+        max_per_external_id: config.max_connections_per_player,
+    }));
+
+    tokio::try_join!(
+        serve_websockets(
+            config.clone(),
+            database.clone(),
+            services.clone(),
+            connections,
+            shutdown.clone()
+        ),
+        serve_api(
+            config.clone(),
+            database.clone(),
+            services.clone(),
+            shutdown.clone()
+        ),
+    )?;
 
+    log::info!("Shutdown complete");
     Ok(())
 }
 
@@ -101,15 +376,40 @@ async fn serve_websockets(
     config: Arc<Config>,
     database: Pool<Postgres>,
     services: Arc<GameServices>,
+    connections: Arc<ConnectionRegistry>,
+    shutdown: Arc<ShutdownCoordinator>,
 ) -> Result<(), Box<dyn Error>> {
     let listener = TcpListener::bind(&config.as_ref().bind).await?;
+    let mut phase = shutdown.subscribe();
+
+    loop {
+        let (stream, peer_address) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown::reached(&mut phase, ShutdownPhase::Draining) => break,
+        };
+
+        let permit = match connections.admit(peer_address.ip()) {
+            Ok(permit) => permit,
+            Err(e) => {
+                log::warn!(
+                    error:% = e,
+                    connections = connections.connection_count();
+                    "Refused connection from {peer_address}."
+                );
+                continue;
+            }
+        };
 
-    while let Ok((stream, peer_address)) = listener.accept().await {
         let config = config.clone();
         let database = database.clone();
         let services = services.clone();
+        let connections = connections.clone();
+        let connection = shutdown.track_connection();
+        let phase = shutdown.subscribe();
 
         tokio::spawn(LogContext::with(async move {
+            let _connection = connection;
+            let _permit = permit;
             LogContext::insert("peer_address", peer_address.to_string());
 
             log::info!(
@@ -117,7 +417,17 @@ async fn serve_websockets(
                 "Started serving client. remote = {peer_address}."
             );
 
-            match serve_client(stream, peer_address, config, database, services).await {
+            match serve_client(
+                stream,
+                peer_address,
+                config,
+                database,
+                services,
+                connections,
+                phase,
+            )
+            .await
+            {
                 Ok(_) => {
                     log::info!(
                         context:serde = LogContext::current();
@@ -135,6 +445,7 @@ async fn serve_websockets(
         log::info!("Incoming connection from: {peer_address}");
     }
 
+    log::info!("Websocket server stopped listening");
     Ok(())
 }
 
@@ -143,28 +454,63 @@ async fn serve_api(
     config: Arc<Config>,
     database: Pool<Postgres>,
     services: Arc<GameServices>,
+    shutdown: Arc<ShutdownCoordinator>,
 ) -> Result<(), Box<dyn Error>> {
-    {
-        let config = config.clone();
-        let state = web::Data::new(AppState { database, services });
+    let api_keys = config
+        .api_keys
+        .iter()
+        .map(|k| (k.key.clone(), k.label.clone()))
+        .chain([(config.api_key.clone(), DEFAULT_API_KEY_LABEL.to_string())])
+        .collect::<HashMap<_, _>>();
+
+    let server = {
+        let state = web::Data::new(AppState {
+            database,
+            services,
+            shutdown: shutdown.clone(),
+        });
 
         HttpServer::new(move || {
             App::new()
                 .app_data(state.clone())
                 .wrap(logging::LogContextMiddleware)
-                .wrap(CheckKey::new(&config.api_key))
+                .wrap(CheckKey::new(api_keys.clone()))
                 .service(healthcheck)
                 .service(get_ban)
                 .service(post_ban)
                 .service(delete_ban)
                 .service(get_ban_by_id)
+                .service(get_ban_events)
+                .service(get_content_deletions)
+                .service(get_content)
+                .service(get_content_by_id)
+                .service(delete_content)
+                .service(delete_player_content)
+                .service(get_restrictions)
+                .service(get_restrictions_by_id)
+                .service(post_restriction)
+                .service(delete_restriction)
+                .service(get_retention)
+                .service(get_rate_limit_offenders)
+                .service(get_client_versions)
+                .service(get_player_characters)
+                .service(get_character)
+                .service(get_character_snapshots)
+                .service(get_cheat_flags)
+                .service(get_cheat_flags_by_id)
                 .service(announcement)
+                .service(post_shutdown)
         })
     }
+    // Signals are handled by the shutdown coordinator which stops the API server last.
+    .disable_signals()
     .bind(&config.api_bind)?
-    .run()
-    .await?;
+    .run();
 
+    shutdown.set_api_handle(server.handle());
+    server.await?;
+
+    log::info!("API server stopped listening");
     Ok(())
 }
 
@@ -174,10 +520,19 @@ enum ClientServeError {
     StreamClosed,
     #[error("Client violated init protocol.")]
     ProtocolViolation,
-    #[error("Client didn't send valid session ticket.")]
-    InvalidSessionTicket,
+    #[error("Client didn't send valid identity claims.")]
+    InvalidIdentityClaims,
+    #[error("Client didn't send a valid client version.")]
+    InvalidClientVersion,
+    #[error("Client writer stopped.")]
+    WriterStopped,
+    #[error("Client didn't complete the handshake in time.")]
+    HandshakeTimeout,
 }
 
+/// Capacity of the channel carrying websocket control frames to the writer.
+const CONTROL_CHANNEL_CAPACITY: usize = 4;
+
 /// Serves a single game client.
 async fn serve_client(
     tcp_stream: TcpStream,
@@ -185,57 +540,73 @@ async fn serve_client(
     config: Arc<Config>,
     database: Pool<Postgres>,
     services: Arc<GameServices>,
+    connections: Arc<ConnectionRegistry>,
+    mut shutdown: watch::Receiver<ShutdownPhase>,
 ) -> Result<(), Box<dyn Error>> {
-    // Sample steam ID, steam session ticket and waygate version off of the HTTP header.
-    let external_id = Arc::from(OnceLock::new());
-    let session_ticket = Arc::from(OnceLock::new());
+    // Everything up to and including the key exchange and the identity backend accepting the
+    // session has to happen before this.
+    let handshake_deadline = Instant::now() + Duration::from_secs(config.handshake_timeout);
+
+    // Sample identity claims and waygate version off of the HTTP header. Requests that don't
+    // carry them are turned away before the upgrade completes.
+    let claims = Arc::from(OnceLock::new());
     let waygate_version = Arc::from(OnceLock::new());
     let header_callback = {
-        let external_id = external_id.clone();
-        let session_ticket = session_ticket.clone();
+        let claims = claims.clone();
         let waygate_version = waygate_version.clone();
+        let identity = services.identity.as_ref();
+        let min_client_version = config.min_client_version.as_ref();
+        let client_versions = &services.client_versions;
 
         move |req: &Request, response: Response| {
-            for (header, value) in req.headers() {
-                if header.as_str() == "x-steam-id" {
-                    let value = value
-                        .to_str()
-                        .expect("Upgrade request missing X-STEAM-ID header");
-                    external_id.set(value.to_string()).unwrap();
-                }
+            let version = client_version(req, min_client_version)
+                .and_then(|version| client_versions.check(version))
+                .map_err(|e| {
+                    log::warn!(
+                        context:serde = LogContext::current(),
+                        error:? = e;
+                        "Rejecting upgrade request with unsupported client version."
+                    );
+                    reject_upgrade(e.status(), &e)
+                })?;
+
+            let value = identity.claims(req).map_err(|e| {
+                log::warn!(
+                    context:serde = LogContext::current(),
+                    error:? = e;
+                    "Could not sample identity claims from upgrade request."
+                );
+                reject_upgrade(StatusCode::UNAUTHORIZED, &e)
+            })?;
 
-                if header.as_str() == "x-steam-session-ticket" {
-                    let value = value
-                        .to_str()
-                        .expect("Upgrade request missing X-STEAM-SESSION-TICKET header");
-                    session_ticket.set(value.to_string()).unwrap();
-                }
-
-                if header.as_str() == "x-waygate-client-version" {
-                    let value = value
-                        .to_str()
-                        .expect("Upgrade request missing X-WAYGATE-CLIENT-VERSION header");
-                    waygate_version.set(value.to_string()).unwrap();
-                }
-            }
+            let _ = waygate_version.set(version);
+            let _ = claims.set(value);
 
             Ok(response)
         }
     };
 
     // Upgrade HTTP request to websockets
-    let (mut sink, mut stream) = tokio_tungstenite::accept_hdr_async(tcp_stream, header_callback)
-        .await?
-        .split();
-
-    let external_id = external_id.get().unwrap();
-    let session_ticket = session_ticket.get().unwrap();
-    let waygate_version = waygate_version.get().unwrap();
-
-    LogContext::insert("external_id", external_id);
-    LogContext::insert("waygate_version", waygate_version);
-
-    let parsed_external_id = external_id.parse::<u64>()?;
+    let (mut sink, mut stream) = timeout_at(
+        handshake_deadline,
+        tokio_tungstenite::accept_hdr_async(tcp_stream, header_callback),
+    )
+    .await
+    .map_err(|_| ClientServeError::HandshakeTimeout)??
+    .split();
+
+    let claims = claims
+        .get()
+        .ok_or(ClientServeError::InvalidIdentityClaims)?;
+    let (waygate_version, version_policy) = waygate_version
+        .get()
+        .ok_or(ClientServeError::InvalidClientVersion)?;
+    let _connected_version = services.client_versions.track(waygate_version);
+
+    LogContext::insert("external_id", claims.external_id.to_string());
+    LogContext::insert("waygate_version", waygate_version.to_string());
+
+    let parsed_external_id = claims.external_id;
 
     let is_banned = match services
         .bans
@@ -262,13 +633,16 @@ async fn serve_client(
     // Handle protocol stuff first like exchanging keys and shit
     let mut protocol = ClientProtocol::new(
         database.clone(),
-        format!("{parsed_external_id:x?}"),
+        protocol::player_external_id(parsed_external_id),
         peer_address.to_string(),
         config.as_ref().try_into()?,
     )?;
 
     // Cycle over stream playing the messaging against our protocol statemachine
-    while let Some(event) = stream.next().await {
+    while let Some(event) = timeout_at(handshake_deadline, stream.next())
+        .await
+        .map_err(|_| ClientServeError::HandshakeTimeout)?
+    {
         let Ok(Message::Binary(data)) = event else {
             return Err(Box::new(ClientServeError::ProtocolViolation));
         };
@@ -282,25 +656,141 @@ async fn serve_client(
         }
     }
 
-    let parsed_session_ticket =
-        hex_to_bytes(session_ticket).ok_or(ClientServeError::InvalidSessionTicket)?;
-    let _steam_session = services
-        .steam
-        .start_session(parsed_external_id, &parsed_session_ticket)?;
+    // Declared ahead of the handler so the handler, and with it the pool entries and push
+    // channel, is dropped first when the connection goes away. The player's other connections
+    // are only pushed out once the identity backend accepted this one.
+    let (mut lease, mut identity) = connections
+        .authenticate_player(services.identity.as_ref(), claims, handshake_deadline)
+        .await?;
+
+    // From here on out reading and writing happen independently. Everything headed for the client,
+    // be it responses or pushes from other players, goes through the outbound channel and gets
+    // encrypted and sent by the writer task.
+    let session = protocol.session_details().unwrap();
+    let protocol = Arc::new(Mutex::new(protocol));
+    let (outbound_tx, outbound_rx) = channel::<Vec<u8>>(config.push_channel_capacity);
+    let (control_tx, control_rx) = channel::<Message>(CONTROL_CHANNEL_CAPACITY);
+    let mut writer = ClientWriter(tokio::spawn(LogContext::inherit(write_messages(
+        sink,
+        protocol.clone(),
+        outbound_rx,
+        control_rx,
+    ))));
+
+    // An earlier connection of the player has to let go of its pool entries and push channel
+    // before this one can register its own.
+    if !lease.takeover(TAKEOVER_TIMEOUT).await {
+        log::warn!(
+            context:serde = LogContext::current();
+            "Previous connection of player did not close in time."
+        );
+    }
 
     // Start serving the, at this point, fully authenticated client.
-    let (push_tx, push_rx) = channel::<Vec<u8>>();
     let mut handler = if is_banned {
         ActiveHandler::Banned(BannedClientHandler::default())
+    } else if *version_policy == VersionPolicy::Degraded {
+        log::info!(
+            context:serde = LogContext::current();
+            "Client runs a degraded Waygate version, only serving the update announcement."
+        );
+        ActiveHandler::Banned(BannedClientHandler::with_announcement(UPDATE_ANNOUNCEMENT))
     } else {
-        ActiveHandler::Default(DefaultClientHandler::new(
+        ActiveHandler::Default(Box::new(DefaultClientHandler::new(
             services.as_ref(),
-            push_tx,
-            protocol.session_details().unwrap(),
-        ))
+            outbound_tx.clone(),
+            session,
+            PlayerRestrictions::new(parsed_external_id.to_string()),
+        )))
     };
 
-    while let Some(event) = stream.next().await {
+    // Clients that stop sending anything, pongs included, are assumed to be gone. Dropping them
+    // frees up their pool entries for other players.
+    let idle_timeout = Duration::from_secs(config.idle_timeout);
+    let ping_period = Duration::from_secs(config.ping_interval);
+    let mut ping_interval = tokio::time::interval_at(Instant::now() + ping_period, ping_period);
+    ping_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_activity = Instant::now();
+
+    loop {
+        let event = tokio::select! {
+            event = stream.next() => match event {
+                Some(event) => {
+                    last_activity = Instant::now();
+                    event
+                }
+                None => break,
+            },
+            _ = ping_interval.tick() => {
+                if last_activity.elapsed() >= idle_timeout {
+                    log::info!(
+                        context:serde = LogContext::current(),
+                        idle_seconds = last_activity.elapsed().as_secs();
+                        "Client went idle, disconnecting..."
+                    );
+                    return Ok(());
+                }
+
+                // A full control channel means earlier pings haven't gone out yet either.
+                let _ = control_tx.try_send(Message::Ping(Default::default()));
+                continue;
+            }
+            outcome = next_validation(&mut identity.validation) => {
+                match outcome {
+                    Some(Ok(())) => log::info!(
+                        context:serde = LogContext::current();
+                        "Identity backend validated session."
+                    ),
+                    Some(Err(e)) => {
+                        log::warn!(
+                            context:serde = LogContext::current(),
+                            error:? = e,
+                            action:? = config.identity_rejection_action;
+                            "Identity backend rejected session."
+                        );
+
+                        match config.identity_rejection_action {
+                            IdentityRejectionAction::Disconnect => return Ok(()),
+                            IdentityRejectionAction::Restrict => {
+                                handler = ActiveHandler::Banned(
+                                    BannedClientHandler::with_announcement(
+                                        IDENTITY_REJECTION_ANNOUNCEMENT,
+                                    ),
+                                );
+                            }
+                        }
+                    }
+                    None => identity.validation = None,
+                }
+
+                continue;
+            }
+            result = &mut writer.0 => {
+                if let Ok(Err(e)) = result {
+                    log::warn!(
+                        context:serde = LogContext::current(),
+                        error:? = e;
+                        "Client writer failed."
+                    );
+                }
+                return Err(Box::new(ClientServeError::WriterStopped));
+            }
+            _ = &mut lease.kicked => {
+                log::info!(
+                    context:serde = LogContext::current();
+                    "Player connected again elsewhere, disconnecting..."
+                );
+                return Ok(());
+            }
+            _ = shutdown::reached(&mut shutdown, ShutdownPhase::Terminating) => {
+                log::info!(
+                    context:serde = LogContext::current();
+                    "Server is shutting down, disconnecting..."
+                );
+                return Ok(());
+            }
+        };
+
         if let ActiveHandler::Default(_) = handler {
             if (services
                 .bans
@@ -316,6 +806,7 @@ async fn serve_client(
             }
         }
         match event {
+            Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => {}
             Ok(Message::Close(_)) => {
                 log::debug!(
                     context:serde = LogContext::current();
@@ -324,14 +815,8 @@ async fn serve_client(
                 return Ok(());
             }
             Ok(Message::Binary(data)) => {
-                // Shove any outbound push messages down the sink.
-                while let Ok(push_message) = push_rx.try_recv() {
-                    let encrypted = protocol.encrypt_message(&push_message)?;
-                    sink.send(Message::Binary(encrypted.into())).await?;
-                }
-
                 // Decrypt received buffer against session parameters for plaintext message.
-                let decrypted = protocol.decrypt_message(data.as_ref())?;
+                let decrypted = protocol.lock().await.decrypt_message(data.as_ref())?;
 
                 // Handle the contents of the message we've received.
                 let reader = MessageReader::new(&decrypted);
@@ -358,7 +843,7 @@ async fn serve_client(
                                     .duration_since(std::time::UNIX_EPOCH)
                                     .expect("Time went backwards")
                                     .as_millis(),
-                                external_id,
+                                parsed_external_id,
                                 sequence,
                                 label,
                             );
@@ -385,8 +870,22 @@ async fn serve_client(
                         };
 
                         // Send response back to client.
-                        let encrypted = protocol.encrypt_message(&response)?;
-                        sink.send(Message::Binary(encrypted.into())).await?;
+                        outbound_tx.send(response).await?;
+
+                        if let ActiveHandler::Default(h) = &handler {
+                            if h.disconnect {
+                                log::info!(
+                                    context:serde = LogContext::current();
+                                    "Client broke a cheat rule, disconnecting..."
+                                );
+
+                                // Let the writer get the response out before the connection goes.
+                                let _ = control_tx.send(Message::Close(None)).await;
+                                let _ =
+                                    timeout_at(Instant::now() + CLOSE_TIMEOUT, &mut writer.0).await;
+                                return Ok(());
+                            }
+                        }
                     }
 
                     // Clients send a push message type to confirm that they've received some
@@ -401,8 +900,9 @@ async fn serve_client(
                     // Clients periodically send these. Client will disconnect if we dont
                     // send one for too long.
                     MessageType::Heartbeat => {
-                        let encrypted = protocol.encrypt_message(MessageBuilder::heartbeat())?;
-                        sink.send(Message::Binary(encrypted.into())).await?;
+                        outbound_tx
+                            .send(MessageBuilder::heartbeat().to_vec())
+                            .await?;
                     }
 
                     _ => {}
@@ -418,16 +918,57 @@ async fn serve_client(
     Err(Box::new(ClientServeError::StreamClosed))
 }
 
-fn hex_to_bytes(s: &str) -> Option<Vec<u8>> {
-    if s.len().is_multiple_of(2) {
-        (0..s.len())
-            .step_by(2)
-            .map(|i| {
-                s.get(i..i + 2)
-                    .and_then(|sub| u8::from_str_radix(sub, 16).ok())
-            })
-            .collect()
-    } else {
-        None
+/// Handle to a client's writer task. Stops the writer when dropped so it can't outlive the
+/// connection it's writing to.
+struct ClientWriter(JoinHandle<Result<(), Box<dyn Error + Send + Sync>>>);
+
+impl Drop for ClientWriter {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Encrypts queued outbound messages and sends them to the client, in the order they were queued.
+/// Websocket control frames are sent as is. A close frame is only sent once the messages queued
+/// ahead of it went out, after which the writer stops.
+async fn write_messages(
+    mut sink: SplitSink<WebSocketStream<TcpStream>, Message>,
+    protocol: Arc<Mutex<ClientProtocol>>,
+    mut outbound_rx: Receiver<Vec<u8>>,
+    mut control_rx: Receiver<Message>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    loop {
+        tokio::select! {
+            message = outbound_rx.recv() => {
+                let Some(message) = message else {
+                    return Ok(());
+                };
+                let encrypted = protocol.lock().await.encrypt_message(&message)?;
+                sink.send(Message::Binary(encrypted.into())).await?;
+            }
+            Some(control) = control_rx.recv() => {
+                if let Message::Close(_) = control {
+                    while let Ok(message) = outbound_rx.try_recv() {
+                        let encrypted = protocol.lock().await.encrypt_message(&message)?;
+                        sink.send(Message::Binary(encrypted.into())).await?;
+                    }
+                    sink.send(control).await?;
+                    return Ok(());
+                }
+
+                sink.send(control).await?
+            }
+        }
+    }
+}
+
+/// Waits for the next validation outcome reported by the identity backend. Never resolves for
+/// backends that don't validate asynchronously.
+async fn next_validation(
+    validation: &mut Option<IdentityValidation>,
+) -> Option<Result<(), IdentityError>> {
+    match validation {
+        Some(validation) => validation.recv().await,
+        None => std::future::pending().await,
     }
 }
@@ -1,7 +1,13 @@
-use std::sync::mpsc::Sender;
-
 use dashmap::DashMap;
+use message::{
+    builder::{self, MessageBuilder},
+    eldenring::{
+        NotifyParams, NotifyParamsSection1, NotifyParamsSection2, ObjectIdentifier, PushParams,
+    },
+};
+use rand::Rng;
 use thiserror::Error;
+use tokio::sync::mpsc::{error::TrySendError, Sender};
 
 use crate::logging::LogContext;
 
@@ -11,6 +17,25 @@ pub enum NotificationChannelPoolError {
     MissingPlayer,
     #[error("Channel error")]
     SendError,
+    #[error("Player's notification channel is full.")]
+    ChannelFull,
+}
+
+/// Builds a push message that shows the message as an announcement to the player.
+pub fn announcement_push(message: &str) -> Result<Vec<u8>, builder::Error> {
+    MessageBuilder::push()
+        .body(PushParams::Notify(NotifyParams {
+            identifier: ObjectIdentifier(rand::rng().random::<i64>()),
+            timestamp: 0,
+            section1: NotifyParamsSection1::Variant1 { unk1: 0, unk2: 0 },
+            section2: NotifyParamsSection2::Variant1 {
+                message: message.to_string(),
+                unk1: 0x0,
+                unk2: 0x0,
+                unk3: 0x0,
+            },
+        }))
+        .build()
 }
 
 #[derive(Default)]
@@ -31,19 +56,22 @@ impl NotificationChannelPool {
             return Err(NotificationChannelPoolError::MissingPlayer);
         };
 
-        channel
-            .send(message)
-            .map_err(|_| NotificationChannelPoolError::SendError)?;
+        channel.try_send(message).map_err(|e| match e {
+            TrySendError::Full(_) => NotificationChannelPoolError::ChannelFull,
+            TrySendError::Closed(_) => NotificationChannelPoolError::SendError,
+        })?;
         Ok(())
     }
 
-    /// Send a notification push message to all connected players.
+    /// Send a notification push message to all connected players. Players whose channel is
+    /// backed up miss out on the message.
     pub fn broadcast(&self, message: Vec<u8>) -> Result<(), NotificationChannelPoolError> {
         self.entries.iter().for_each(|e| {
-            if let Err(e) = e.value().send(message.clone()) {
+            if let Err(err) = e.value().try_send(message.clone()) {
                 log::error!(
                     context:serde = LogContext::current(),
-                    error:? = e;
+                    player_id = *e.key(),
+                    error:? = err;
                     "Could not broadcast message to user."
                 );
             }
@@ -52,25 +80,32 @@ impl NotificationChannelPool {
         Ok(())
     }
 
+    /// Registers the player's push channel, replacing any channel of an earlier connection.
     pub fn insert(
         &self,
         player_id: i32,
         entry: Sender<Vec<u8>>,
     ) -> NotificationChannelPoolToken<'_> {
-        self.entries.insert(player_id, entry);
-        NotificationChannelPoolToken(self, player_id)
+        self.entries.insert(player_id, entry.clone());
+        NotificationChannelPoolToken(self, player_id, entry)
     }
 
-    pub fn remove(&self, player: i32) -> Result<(), NotificationChannelPoolError> {
+    /// Removes the player's push channel, unless it has since been replaced by another one.
+    pub fn remove(
+        &self,
+        player: i32,
+        entry: &Sender<Vec<u8>>,
+    ) -> Result<(), NotificationChannelPoolError> {
         self.entries
-            .remove(&player)
+            .remove_if(&player, |_, current| current.same_channel(entry))
             .ok_or(NotificationChannelPoolError::MissingPlayer)?;
         Ok(())
     }
 }
 
-/// Represents an entry in the sign pool. Removes corresponding entry when dropped.
-pub struct NotificationChannelPoolToken<'a>(&'a NotificationChannelPool, pub i32);
+/// Represents an entry in the notification channel pool. Removes corresponding entry when
+/// dropped.
+pub struct NotificationChannelPoolToken<'a>(&'a NotificationChannelPool, pub i32, Sender<Vec<u8>>);
 
 impl Drop for NotificationChannelPoolToken<'_> {
     fn drop(&mut self) {
@@ -78,6 +113,27 @@ impl Drop for NotificationChannelPoolToken<'_> {
             context:serde = LogContext::current();
             "Destroying notification channel pool token"
         );
-        let _ = self.0.remove(self.1);
+        let _ = self.0.remove(self.1, &self.2);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc::channel;
+
+    use super::NotificationChannelPool;
+
+    #[test]
+    fn stale_token_keeps_newer_channel() {
+        let pool = NotificationChannelPool::default();
+        let (old_tx, _old_rx) = channel(1);
+        let (new_tx, mut new_rx) = channel(1);
+
+        let old = pool.insert(1, old_tx);
+        let _new = pool.insert(1, new_tx);
+        drop(old);
+
+        pool.notify_player(1, vec![1]).unwrap();
+        assert_eq!(new_rx.try_recv().unwrap(), vec![1]);
     }
 }
@@ -1,9 +1,13 @@
 mod crypto;
 
-use std::time::{self};
+use std::{
+    ffi::c_void,
+    time::{self, Duration},
+};
 
 use base64::{prelude::BASE64_STANDARD, Engine};
 use crypto::ClientProtocolCrypto;
+use libsodium_sys::sodium_memcmp;
 
 use message::{
     builder::MessageBuilder,
@@ -17,6 +21,9 @@ use sqlx::{Pool, Postgres, Row};
 
 use crate::Config;
 
+/// How often expired sessions are purged from the database.
+pub const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Received invalid hello message.")]
@@ -31,6 +38,8 @@ pub enum Error {
     AcquirePlayer,
     #[error("Could not acquire session record.")]
     AcquireSession,
+    #[error("Session could not be restored. {0}")]
+    RestoreSession(&'static str),
     #[error("Failed building message.")]
     MessageBuilder,
     #[error("Failed encrypting message.")]
@@ -154,12 +163,32 @@ impl ClientProtocol {
                     }
                     RequestParams::RestoreSession(request) => {
                         let session = self
-                            .retrieve_session(
-                                request.session_data.identifier,
-                                &request.session_data.cookie,
-                            )
+                            .retrieve_session(request.session_data.identifier)
+                            .await
+                            .map_err(|_| Error::AcquireSession)?
+                            .ok_or(Error::RestoreSession("unknown session"))?;
+
+                        if session.player_id != player_id {
+                            return Err(Error::RestoreSession("session belongs to other player"));
+                        }
+
+                        if !cookies_match(&session.cookie, &request.session_data.cookie) {
+                            return Err(Error::RestoreSession("cookie mismatch"));
+                        }
+
+                        if session.valid_until < now.as_secs() as i64 {
+                            return Err(Error::RestoreSession("session expired"));
+                        }
+
+                        // Rotate the cookie so the one we've just accepted can't be replayed.
+                        // Only one of several concurrent restores with the same cookie gets to.
+                        let renewed = self
+                            .renew_session(&session, &cookie, valid_until, now.as_secs() as i64)
                             .await
                             .map_err(|_| Error::AcquireSession)?;
+                        if !renewed {
+                            return Err(Error::RestoreSession("cookie already used"));
+                        }
 
                         self.session = Some(ClientSession {
                             player_id,
@@ -273,16 +302,78 @@ impl ClientProtocol {
     async fn retrieve_session(
         &self,
         session_id: i64,
-        _cookie: &str,
-    ) -> Result<SessionRecord, Box<dyn std::error::Error>> {
+    ) -> Result<Option<SessionRecord>, Box<dyn std::error::Error>> {
         Ok(sqlx::query_as::<_, SessionRecord>(
-            "SELECT * FROM sessions WHERE session_id = $1 -- AND cookie = $2",
+            "SELECT session_id, player_id, cookie, valid_until FROM sessions WHERE session_id = $1",
         )
         .bind(session_id)
-        // .bind(cookie)
-        .fetch_one(&self.database)
+        .fetch_optional(&self.database)
         .await?)
     }
+
+    /// Swaps the cookie of the session for a new one, provided it still has the cookie it was
+    /// retrieved with and hasn't expired since. Returns false otherwise.
+    async fn renew_session(
+        &self,
+        session: &SessionRecord,
+        cookie: &str,
+        valid_until: i64,
+        now: i64,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(sqlx::query(
+            "UPDATE sessions SET cookie = $4, valid_until = $5
+            WHERE session_id = $1 AND player_id = $2 AND cookie = $3 AND valid_until >= $6
+            RETURNING session_id",
+        )
+        .bind(session.session_id)
+        .bind(session.player_id)
+        .bind(&session.cookie)
+        .bind(cookie)
+        .bind(valid_until)
+        .bind(now)
+        .fetch_optional(&self.database)
+        .await?
+        .is_some())
+    }
+}
+
+/// Formats an external ID the way it is stored in the players table.
+pub fn player_external_id(external_id: u64) -> String {
+    format!("{external_id:x?}")
+}
+
+/// Reverses [player_external_id], bans and restrictions go by the unformatted external ID.
+pub fn parse_player_external_id(external_id: &str) -> Result<u64, std::num::ParseIntError> {
+    u64::from_str_radix(external_id, 16)
+}
+
+/// Periodically deletes sessions that can no longer be restored.
+pub async fn sweep_expired_sessions(database: Pool<Postgres>) {
+    let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let now = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        match sqlx::query("DELETE FROM sessions WHERE valid_until < $1")
+            .bind(now)
+            .execute(&database)
+            .await
+        {
+            Ok(result) => log::info!(
+                swept = result.rows_affected();
+                "Swept expired sessions."
+            ),
+            Err(e) => log::error!(
+                error:? = e;
+                "Could not sweep expired sessions."
+            ),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -313,6 +404,18 @@ fn generate_session_cookie() -> String {
     encode_session_cookie(&cookie)
 }
 
+/// Compares cookies without leaking how much of the candidate matched through timing.
+fn cookies_match(expected: &str, candidate: &str) -> bool {
+    expected.len() == candidate.len()
+        && unsafe {
+            sodium_memcmp(
+                expected.as_ptr() as *const c_void,
+                candidate.as_ptr() as *const c_void,
+                expected.len(),
+            )
+        } == 0
+}
+
 fn encode_session_cookie(cookie: &[u8]) -> String {
     format!("{cookie:02x?}")
         .replace(['[', ']', ' ', ','], "")
@@ -327,4 +430,26 @@ struct PlayerRecord {
 #[derive(sqlx::FromRow, Debug)]
 struct SessionRecord {
     session_id: i64,
+    player_id: i32,
+    cookie: String,
+    valid_until: i64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cookies_match, generate_session_cookie};
+
+    #[test]
+    fn cookies_match_identical() {
+        let cookie = generate_session_cookie();
+
+        assert!(cookies_match(&cookie, &cookie.clone()));
+    }
+
+    #[test]
+    fn cookies_dont_match_differing() {
+        assert!(!cookies_match("abcd", "abce"));
+        assert!(!cookies_match("abcd", "abc"));
+        assert!(!cookies_match("abcd", ""));
+    }
 }
@@ -0,0 +1,270 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::File,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{protocol::ClientSession, services::eldenring::GameServices};
+
+/// How often buckets that have filled back up are dropped.
+const BUCKET_SWEEP_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Budget for a single request type. Players can burst up to `capacity` requests after which
+/// they regain `refill_per_minute` requests every minute.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct RateLimit {
+    pub capacity: f64,
+    pub refill_per_minute: f64,
+}
+
+impl RateLimit {
+    fn refill_per_second(&self) -> f64 {
+        self.refill_per_minute / 60.0
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RateLimitConfig {
+    /// Budgets keyed by request type, eg. `CreateBloodMessage`. Request types without a budget
+    /// aren't limited.
+    #[serde(default)]
+    pub limits: HashMap<String, RateLimit>,
+}
+
+impl RateLimitConfig {
+    /// A budget below a single request locks the request type out for good, one that doesn't
+    /// refill only lets the first burst through.
+    fn validate(&self) -> Result<(), String> {
+        for (request, limit) in &self.limits {
+            if !limit.capacity.is_finite() || limit.capacity < 1.0 {
+                return Err(format!(
+                    "capacity of {request} has to be at least 1, got {}",
+                    limit.capacity
+                ));
+            }
+
+            if !limit.refill_per_minute.is_finite() || limit.refill_per_minute <= 0.0 {
+                return Err(format!(
+                    "refill_per_minute of {request} has to be above 0, got {}",
+                    limit.refill_per_minute
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.capacity,
+            last_refill: now,
+        }
+    }
+
+    /// Takes a token from the bucket if there is one.
+    fn take(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.refill_per_second()).min(limit.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the bucket would be back at capacity by now, in which case it's no different
+    /// from a fresh one.
+    fn is_full(&self, limit: &RateLimit, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens + elapsed * limit.refill_per_second() >= limit.capacity
+    }
+}
+
+/// Players that went over their budget since the server started.
+#[derive(Clone, Debug, Serialize)]
+pub struct RateLimitOffender {
+    pub player_id: i32,
+    /// External ID as the players table knows it.
+    pub external_id: String,
+    pub rejected: u64,
+    pub rejected_by_request: HashMap<&'static str, u64>,
+    pub last_rejected_at: i64,
+}
+
+/// Token bucket rate limiter keyed on player and request type.
+#[derive(Default)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: DashMap<(i32, &'static str), TokenBucket>,
+    offenders: DashMap<i32, RateLimitOffender>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let config: RateLimitConfig = serde_yaml::from_reader(File::open(path)?)?;
+        config.validate()?;
+
+        Ok(Self::new(config))
+    }
+
+    /// Takes a token for the request type from the player's bucket. Returns false if the player
+    /// is over their budget, which is recorded against the player.
+    pub fn check(&self, session: &ClientSession, request: &'static str) -> bool {
+        let Some(limit) = self.config.limits.get(request) else {
+            return true;
+        };
+
+        let now = Instant::now();
+        let allowed = self
+            .buckets
+            .entry((session.player_id, request))
+            .or_insert_with(|| TokenBucket::new(limit, now))
+            .take(limit, now);
+
+        if !allowed {
+            self.record_offense(session, request);
+        }
+
+        allowed
+    }
+
+    fn record_offense(&self, session: &ClientSession, request: &'static str) {
+        let mut offender =
+            self.offenders
+                .entry(session.player_id)
+                .or_insert_with(|| RateLimitOffender {
+                    player_id: session.player_id,
+                    external_id: session.external_id.clone(),
+                    rejected: 0,
+                    rejected_by_request: Default::default(),
+                    last_rejected_at: 0,
+                });
+
+        offender.rejected += 1;
+        *offender.rejected_by_request.entry(request).or_default() += 1;
+        offender.last_rejected_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+    }
+
+    /// Offenders with at least the given amount of rejected requests, worst first.
+    pub fn offenders(&self, min_rejected: u64) -> Vec<RateLimitOffender> {
+        let mut offenders = self
+            .offenders
+            .iter()
+            .filter(|o| o.rejected >= min_rejected)
+            .map(|o| o.value().clone())
+            .collect::<Vec<_>>();
+
+        offenders.sort_by_key(|o| Reverse(o.rejected));
+        offenders
+    }
+
+    /// Drops buckets that have refilled completely so they don't pile up for every player that
+    /// ever connected.
+    fn sweep(&self) {
+        let now = Instant::now();
+        self.buckets.retain(|(_, request), bucket| {
+            self.config
+                .limits
+                .get(*request)
+                .is_some_and(|limit| !bucket.is_full(limit, now))
+        });
+    }
+}
+
+pub async fn sweep_rate_limit_buckets(services: Arc<GameServices>) {
+    let mut interval = tokio::time::interval(BUCKET_SWEEP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        services.rate_limiter.sweep();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{RateLimit, RateLimitConfig, TokenBucket};
+
+    const LIMIT: RateLimit = RateLimit {
+        capacity: 2.0,
+        refill_per_minute: 6.0,
+    };
+
+    #[test]
+    fn allows_burst_up_to_capacity() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(&LIMIT, now);
+
+        assert!(bucket.take(&LIMIT, now));
+        assert!(bucket.take(&LIMIT, now));
+        assert!(!bucket.take(&LIMIT, now));
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(&LIMIT, now);
+        assert!(bucket.take(&LIMIT, now));
+        assert!(bucket.take(&LIMIT, now));
+
+        // 6 per minute is one every 10 seconds.
+        assert!(!bucket.take(&LIMIT, now + Duration::from_secs(5)));
+        assert!(bucket.take(&LIMIT, now + Duration::from_secs(11)));
+        assert!(!bucket.is_full(&LIMIT, now + Duration::from_secs(11)));
+        assert!(bucket.is_full(&LIMIT, now + Duration::from_secs(40)));
+    }
+
+    #[test]
+    fn rejects_budgets_that_lock_out_or_never_refill() {
+        for limit in [
+            "{ capacity: 0, refill_per_minute: 6 }",
+            "{ capacity: -1, refill_per_minute: 6 }",
+            "{ capacity: .nan, refill_per_minute: 6 }",
+            "{ capacity: 2, refill_per_minute: 0 }",
+            "{ capacity: 2, refill_per_minute: .inf }",
+        ] {
+            let config: RateLimitConfig =
+                serde_yaml::from_str(&format!("limits: {{ CreateBloodMessage: {limit} }}"))
+                    .unwrap();
+            assert!(config.validate().is_err(), "{limit}");
+        }
+
+        let config: RateLimitConfig = serde_yaml::from_str(
+            "limits: { CreateBloodMessage: { capacity: 2, refill_per_minute: 6 } }",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+    }
+}
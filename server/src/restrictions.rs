@@ -0,0 +1,259 @@
+use std::{
+    collections::HashSet,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use message::eldenring::RequestParams;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres, Row};
+
+/// How long a connection holds on to a player's restrictions before fetching them again.
+pub const RESTRICTIONS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+const ACTIVE_CONDITION: &str = "(expires_at IS NULL OR expires_at > EXTRACT(EPOCH FROM NOW()))";
+
+/// A single feature a player can be barred from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Restriction {
+    /// Cannot write or rate blood messages.
+    BloodMessages,
+    /// Cannot invade other players.
+    Invasions,
+    /// Cannot put down summon signs.
+    Signs,
+    /// Cannot take part in quickmatches.
+    QuickMatch,
+    /// Blood messages, bloodstains and ghosts the player leaves behind are only shown to
+    /// themselves.
+    Shadow,
+}
+
+impl Restriction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Restriction::BloodMessages => "bloodmessages",
+            Restriction::Invasions => "invasions",
+            Restriction::Signs => "signs",
+            Restriction::QuickMatch => "quickmatch",
+            Restriction::Shadow => "shadow",
+        }
+    }
+
+    /// Restriction that bars the player from making this request, if any.
+    pub fn for_request(request: &RequestParams) -> Option<Self> {
+        match request {
+            RequestParams::CreateBloodMessage(_) | RequestParams::EvaluateBloodMessage(_) => {
+                Some(Restriction::BloodMessages)
+            }
+
+            RequestParams::GetBreakInTargetList(_) | RequestParams::BreakInTarget(_) => {
+                Some(Restriction::Invasions)
+            }
+
+            RequestParams::CreateSign(_)
+            | RequestParams::CreateMatchAreaSign(_)
+            | RequestParams::UpdateSign(_) => Some(Restriction::Signs),
+
+            RequestParams::SearchQuickMatch(_)
+            | RequestParams::RegisterQuickMatch(_)
+            | RequestParams::JoinQuickMatch(_) => Some(Restriction::QuickMatch),
+
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Restriction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Restriction::BloodMessages,
+            Restriction::Invasions,
+            Restriction::Signs,
+            Restriction::QuickMatch,
+            Restriction::Shadow,
+        ]
+        .into_iter()
+        .find(|r| r.as_str() == s)
+        .ok_or_else(|| format!("Unknown restriction {s}"))
+    }
+}
+
+pub struct RestrictionService {
+    pub database: Pool<Postgres>,
+}
+
+impl RestrictionService {
+    pub fn new(database: Pool<Postgres>) -> Self {
+        Self { database }
+    }
+
+    /// Restricts a player, replacing an existing restriction of the same kind. Omitting the expiry
+    /// makes the restriction permanent.
+    pub async fn add_restriction(
+        &self,
+        external_id: &str,
+        restriction: Restriction,
+        reason: Option<&str>,
+        expires_at: Option<i64>,
+        moderator: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO player_restrictions (external_id, restriction, reason, expires_at, moderator)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (external_id, restriction) DO UPDATE SET
+                created_at = EXTRACT(EPOCH FROM NOW()),
+                reason = EXCLUDED.reason,
+                expires_at = EXCLUDED.expires_at,
+                moderator = EXCLUDED.moderator",
+        )
+        .bind(external_id)
+        .bind(restriction.as_str())
+        .bind(reason)
+        .bind(expires_at)
+        .bind(moderator)
+        .execute(&self.database)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Lifts a restriction. Returns false if the player wasn't restricted like that.
+    pub async fn delete_restriction(
+        &self,
+        external_id: &str,
+        restriction: Restriction,
+    ) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query(
+            "DELETE FROM player_restrictions WHERE external_id = $1 AND restriction = $2",
+        )
+        .bind(external_id)
+        .bind(restriction.as_str())
+        .execute(&self.database)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    /// Retrieves the restrictions currently in effect for a player.
+    pub async fn get_restrictions(
+        &self,
+        external_id: &str,
+    ) -> Result<Vec<RestrictionRecord>, sqlx::Error> {
+        let restrictions = sqlx::query_as::<_, RestrictionRecord>(&format!(
+            "SELECT * FROM player_restrictions WHERE external_id = $1 AND {ACTIVE_CONDITION}"
+        ))
+        .bind(external_id)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(restrictions)
+    }
+
+    pub async fn list_restrictions(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<RestrictionRecord>, sqlx::Error> {
+        let restrictions = sqlx::query_as::<_, RestrictionRecord>(&format!(
+            "SELECT * FROM player_restrictions WHERE {ACTIVE_CONDITION}
+            ORDER BY external_id, restriction LIMIT $1 OFFSET $2"
+        ))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(restrictions)
+    }
+
+    pub async fn get_total(&self) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query(&format!(
+            "SELECT COUNT(*) FROM player_restrictions WHERE {ACTIVE_CONDITION}"
+        ))
+        .fetch_one(&self.database)
+        .await?
+        .get(0);
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct RestrictionRecord {
+    pub external_id: String,
+    pub restriction: String,
+    pub reason: Option<String>,
+    pub moderator: Option<String>,
+    pub expires_at: Option<i64>,
+    pub created_at: i64,
+}
+
+/// A connected player's restrictions. Periodically refetched so changes made through the API
+/// apply to players that are already online.
+pub struct PlayerRestrictions {
+    external_id: String,
+    active: HashSet<Restriction>,
+    refreshed_at: Option<Instant>,
+}
+
+impl PlayerRestrictions {
+    pub fn new(external_id: String) -> Self {
+        Self {
+            external_id,
+            active: HashSet::new(),
+            refreshed_at: None,
+        }
+    }
+
+    /// Refetches the restrictions if the ones we have are stale.
+    pub async fn refresh(&mut self, service: &RestrictionService) -> Result<(), sqlx::Error> {
+        if self
+            .refreshed_at
+            .is_some_and(|r| r.elapsed() < RESTRICTIONS_REFRESH_INTERVAL)
+        {
+            return Ok(());
+        }
+
+        self.active = service
+            .get_restrictions(&self.external_id)
+            .await?
+            .iter()
+            .filter_map(|r| r.restriction.parse().ok())
+            .collect();
+        self.refreshed_at = Some(Instant::now());
+
+        Ok(())
+    }
+
+    pub fn contains(&self, restriction: Restriction) -> bool {
+        self.active.contains(&restriction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Restriction;
+
+    #[test]
+    fn restriction_names_round_trip() {
+        for restriction in [
+            Restriction::BloodMessages,
+            Restriction::Invasions,
+            Restriction::Signs,
+            Restriction::QuickMatch,
+            Restriction::Shadow,
+        ] {
+            assert_eq!(restriction.as_str().parse(), Ok(restriction));
+        }
+    }
+
+    #[test]
+    fn unknown_restriction_is_rejected() {
+        assert!("everything".parse::<Restriction>().is_err());
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+
+use crate::services::eldenring::GameServices;
+
+/// Policies are read from disk on every run so they can be tuned without a restart.
+const RETENTION_CONFIG: &str = "config/retention.yml";
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Tables holding content uploaded by players.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionTable {
+    #[serde(rename = "bloodmessages")]
+    BloodMessages,
+    Bloodstains,
+    #[serde(rename = "ghostdata")]
+    GhostData,
+    PlayerEquipments,
+    CharacterSnapshots,
+}
+
+impl RetentionTable {
+    fn table(&self) -> &'static str {
+        match self {
+            RetentionTable::BloodMessages => "bloodmessages",
+            RetentionTable::Bloodstains => "bloodstains",
+            RetentionTable::GhostData => "ghostdata",
+            RetentionTable::PlayerEquipments => "player_equipments",
+            RetentionTable::CharacterSnapshots => "character_snapshots",
+        }
+    }
+
+    fn id_column(&self) -> &'static str {
+        match self {
+            RetentionTable::BloodMessages => "bloodmessage_id",
+            RetentionTable::Bloodstains => "bloodstain_id",
+            RetentionTable::GhostData => "ghostdata_id",
+            RetentionTable::PlayerEquipments => "player_equipments_id",
+            RetentionTable::CharacterSnapshots => "character_snapshot_id",
+        }
+    }
+
+    /// Column the content is pooled by when served to clients. Player equipments are pooled by
+    /// their pool rather than a play region.
+    fn region_column(&self) -> &'static str {
+        match self {
+            RetentionTable::PlayerEquipments => "pool",
+            _ => "play_region",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetentionConfig {
+    /// Rows removed per statement, keeps locks on busy tables short.
+    pub batch_size: i64,
+    #[serde(default)]
+    pub tables: BTreeMap<RetentionTable, RetentionPolicy>,
+}
+
+impl RetentionConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let config: Self = serde_yaml::from_reader(File::open(path)?)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.batch_size <= 0 {
+            return Err(format!(
+                "batch_size has to be at least 1, got {}",
+                self.batch_size
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Limits applied to a single table. Omitted limits aren't enforced. When over a limit the
+/// oldest rows are removed first.
+#[derive(Debug, Default, Deserialize)]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<i64>,
+    pub max_per_play_region: Option<i64>,
+    pub max_per_player: Option<i64>,
+}
+
+/// Outcome of a single pruning run.
+#[derive(Clone, Debug, Serialize)]
+pub struct RetentionRun {
+    pub started_at: i64,
+    pub duration_ms: u64,
+    pub tables: Vec<TablePruneStats>,
+    /// Set if the run was cut short. Tables pruned before the error are still listed.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TablePruneStats {
+    pub table: RetentionTable,
+    pub expired: u64,
+    pub over_play_region_limit: u64,
+    pub over_player_limit: u64,
+}
+
+pub struct RetentionService {
+    pub database: Pool<Postgres>,
+    last_run: RwLock<Option<RetentionRun>>,
+}
+
+impl RetentionService {
+    pub fn new(database: Pool<Postgres>) -> Self {
+        Self {
+            database,
+            last_run: Default::default(),
+        }
+    }
+
+    pub fn last_run(&self) -> Option<RetentionRun> {
+        self.last_run.read().unwrap().clone()
+    }
+
+    /// Prunes all tables according to the retention config and records the stats of the run.
+    pub async fn run(&self) -> RetentionRun {
+        let started = Instant::now();
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let mut tables = Vec::new();
+        let error = match self.prune(&mut tables).await {
+            Ok(()) => None,
+            Err(e) => Some(e.to_string()),
+        };
+
+        let run = RetentionRun {
+            started_at,
+            duration_ms: started.elapsed().as_millis() as u64,
+            tables,
+            error,
+        };
+        *self.last_run.write().unwrap() = Some(run.clone());
+
+        run
+    }
+
+    async fn prune(
+        &self,
+        stats: &mut Vec<TablePruneStats>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let config = RetentionConfig::load(RETENTION_CONFIG)?;
+
+        for (table, policy) in config.tables.iter() {
+            let mut table_stats = TablePruneStats {
+                table: *table,
+                expired: 0,
+                over_play_region_limit: 0,
+                over_player_limit: 0,
+            };
+
+            if let Some(max_age_days) = policy.max_age_days {
+                let query = format!(
+                    "DELETE FROM {0} WHERE {1} IN (
+                        SELECT {1} FROM {0}
+                        WHERE created_at < EXTRACT(EPOCH FROM NOW()) - $1
+                        LIMIT $2
+                    )",
+                    table.table(),
+                    table.id_column(),
+                );
+                table_stats.expired = self
+                    .delete_batched(&query, max_age_days * SECONDS_PER_DAY, config.batch_size)
+                    .await?;
+            }
+
+            if let Some(max_per_play_region) = policy.max_per_play_region {
+                table_stats.over_play_region_limit = self
+                    .delete_over_limit(
+                        *table,
+                        table.region_column(),
+                        max_per_play_region,
+                        config.batch_size,
+                    )
+                    .await?;
+            }
+
+            if let Some(max_per_player) = policy.max_per_player {
+                table_stats.over_player_limit = self
+                    .delete_over_limit(*table, "player_id", max_per_player, config.batch_size)
+                    .await?;
+            }
+
+            stats.push(table_stats);
+        }
+
+        Ok(())
+    }
+
+    /// Repeatedly runs a delete query binding the limit as $1 and the batch size as $2 until it
+    /// stops filling up batches. Returns the total amount of rows deleted.
+    async fn delete_batched(
+        &self,
+        query: &str,
+        limit: i64,
+        batch_size: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let mut total = 0;
+        loop {
+            let deleted = sqlx::query(query)
+                .bind(limit)
+                .bind(batch_size)
+                .execute(&self.database)
+                .await?
+                .rows_affected();
+
+            total += deleted;
+            if (deleted as i64) < batch_size {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Deletes the rows over the limit for their group of the partition column. The rows are
+    /// looked up once and then deleted in batches. Returns the amount of rows deleted.
+    async fn delete_over_limit(
+        &self,
+        table: RetentionTable,
+        partition_column: &str,
+        limit: i64,
+        batch_size: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let ids: Vec<i64> = sqlx::query_scalar(&over_limit_query(table, partition_column))
+            .bind(limit)
+            .fetch_all(&self.database)
+            .await?;
+
+        let query = format!(
+            "DELETE FROM {} WHERE {} = ANY($1)",
+            table.table(),
+            table.id_column()
+        );
+        let mut total = 0;
+        for batch in ids.chunks(batch_size as usize) {
+            total += sqlx::query(&query)
+                .bind(batch)
+                .execute(&self.database)
+                .await?
+                .rows_affected();
+        }
+
+        Ok(total)
+    }
+}
+
+/// Selects the oldest rows of every group of the given column holding more than $1 rows. Only
+/// groups over the limit get ranked.
+fn over_limit_query(table: RetentionTable, partition_column: &str) -> String {
+    format!(
+        "SELECT {1} FROM (
+            SELECT {1}, row_number() OVER (
+                PARTITION BY {2} ORDER BY created_at DESC, {1} DESC
+            ) AS position
+            FROM {0}
+            WHERE {2} IN (SELECT {2} FROM {0} GROUP BY {2} HAVING COUNT(*) > $1)
+        ) ranked
+        WHERE position > $1",
+        table.table(),
+        table.id_column(),
+        partition_column,
+    )
+}
+
+/// Periodically prunes the player content tables.
+pub async fn prune_periodically(services: Arc<GameServices>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        let run = services.retention.run().await;
+        match run.error {
+            None => log::info!(
+                duration_ms = run.duration_ms;
+                "Pruned player content."
+            ),
+            Some(error) => log::error!(
+                error = error;
+                "Could not prune player content."
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RetentionConfig, RetentionTable};
+
+    #[test]
+    fn parses_bundled_config() {
+        let config: RetentionConfig =
+            serde_yaml::from_str(include_str!("../../config/retention.yml")).unwrap();
+
+        assert!(config.validate().is_ok());
+        assert!(config.tables.contains_key(&RetentionTable::BloodMessages));
+        assert!(config
+            .tables
+            .contains_key(&RetentionTable::PlayerEquipments));
+    }
+
+    #[test]
+    fn rejects_empty_batches() {
+        let config: RetentionConfig = serde_yaml::from_str("batch_size: 0").unwrap();
+
+        assert!(config.validate().is_err());
+    }
+}
@@ -1,44 +1,146 @@
 use sqlx::{Pool, Postgres};
 use thiserror::Error;
 
+use std::{sync::Arc, time::Duration};
+
 use breakin::BreakInPool;
+use match_density::{MatchDensity, MatchDensityCache};
+use matchingticket::MatchingTicketPool;
+use message_selection::BloodMessageSelection;
 use quickmatch::QuickMatchPool;
 use sign::SignPool;
 use visit::VisitorPool;
 
-use crate::{bans::BanService, notification::NotificationChannelPool, steam::SteamServer};
+use crate::{
+    bans::BanService, characters::CharacterService, cheats::CheatService,
+    client_version::ClientVersions, content::ContentService, identity::IdentityProvider,
+    notification::NotificationChannelPool, rate_limit::RateLimiter,
+    restrictions::RestrictionService, retention::RetentionService,
+};
 
 pub mod area;
 pub mod breakin;
+pub mod match_density;
+pub mod matchingticket;
+pub mod message_filter;
+pub mod message_selection;
 pub mod quickmatch;
 pub mod sign;
 pub mod visit;
 pub mod weapon;
 
+/// Amount of entries returned for bloodmessage, bloodstain and ghost list requests.
+const CONTENT_LIST_LIMIT: i64 = 64;
+
+/// `grouped` CTE of the bloodmessage, bloodstain and ghost list queries. Picks up to `$4` entries
+/// in the play regions `$1` that share a group password with `$3`, starting at a random point
+/// taken from the `pivot` CTE. Shadowed entries are only returned to their creator `$2`. A limit
+/// of zero keeps Postgres from running the lookup at all.
+pub fn grouped_content_cte(table: &str, id_column: &str) -> String {
+    format!(
+        "grouped AS (
+            SELECT {id_column} FROM (
+                SELECT {id_column}, rnd
+                FROM {table}
+                WHERE play_region = ANY($1::int[]) AND group_passwords && $3::text[]
+                    AND rnd >= (SELECT r FROM pivot)
+                    AND (NOT shadowed OR player_id = $2)
+                ORDER BY rnd
+                LIMIT $4
+            ) group_above
+            UNION ALL
+            SELECT {id_column} FROM (
+                SELECT {id_column}, rnd
+                FROM {table}
+                WHERE play_region = ANY($1::int[]) AND group_passwords && $3::text[]
+                    AND rnd < (SELECT r FROM pivot)
+                    AND (NOT shadowed OR player_id = $2)
+                ORDER BY rnd
+                LIMIT $4
+            ) group_below
+            LIMIT $4
+         )"
+    )
+}
+
 pub struct GameServices {
     pub database: Pool<Postgres>,
-    pub steam: SteamServer,
+    pub identity: Box<dyn IdentityProvider>,
     pub bans: BanService,
+    pub characters: CharacterService,
+    pub cheats: CheatService,
+    pub restrictions: RestrictionService,
+    pub content: ContentService,
+    pub retention: RetentionService,
+    pub rate_limiter: RateLimiter,
+    pub client_versions: ClientVersions,
+    pub bloodmessage_selection: BloodMessageSelection,
+    /// Share of content list responses reserved for content sharing a group password with the
+    /// requesting player.
+    pub group_content_share: f64,
     pub pool_sign: SignPool,
     pub pool_breakin: BreakInPool,
     pub pool_visitor: VisitorPool,
     pub pool_quickmatch: QuickMatchPool,
+    pub pool_matching_ticket: MatchingTicketPool,
     pub notifications: NotificationChannelPool,
+    pub match_density: MatchDensityCache,
 }
 
 impl GameServices {
-    pub fn new(database: Pool<Postgres>) -> Result<GameServices, Box<dyn std::error::Error>> {
+    pub fn new(
+        database: Pool<Postgres>,
+        identity: Box<dyn IdentityProvider>,
+        bloodmessage_selection: BloodMessageSelection,
+        group_content_share: f64,
+        rate_limiter: RateLimiter,
+        client_versions: ClientVersions,
+        snapshot_interval: Duration,
+    ) -> Result<GameServices, Box<dyn std::error::Error>> {
         Ok(GameServices {
             bans: BanService::new(database.clone()),
+            characters: CharacterService::new(database.clone(), snapshot_interval),
+            cheats: CheatService::new(database.clone()),
+            restrictions: RestrictionService::new(database.clone()),
+            content: ContentService::new(database.clone()),
+            retention: RetentionService::new(database.clone()),
+            rate_limiter,
+            client_versions,
+            bloodmessage_selection,
+            group_content_share,
             database,
-            steam: SteamServer::init()?,
+            identity,
             pool_sign: SignPool::default(),
             pool_breakin: BreakInPool::default(),
             pool_visitor: VisitorPool::default(),
             pool_quickmatch: QuickMatchPool::default(),
+            pool_matching_ticket: MatchingTicketPool::default(),
             notifications: NotificationChannelPool::default(),
+            match_density: MatchDensityCache::default(),
+        })
+    }
+
+    /// Matchmaking activity per play region, computed from the sign and breakin pools. Signs count
+    /// as blue activity, invadeable hosts as red activity. Hunters are left out as the visitor
+    /// pool doesn't know where they are.
+    pub fn match_density(&self) -> Arc<MatchDensity> {
+        self.match_density.get_or_compute(|| {
+            let mut density = MatchDensity::default();
+            density.add_blue(self.pool_sign.play_region_counts());
+            density.add_red(self.pool_breakin.play_region_counts());
+            density
         })
     }
+
+    /// How many entries of a content list response may be taken up by group content. None at
+    /// all for requests without group passwords, as nothing could match them.
+    pub fn group_content_limit(&self, group_passwords: &[String]) -> i64 {
+        if group_passwords.is_empty() {
+            return 0;
+        }
+
+        (self.group_content_share.clamp(0.0, 1.0) * CONTENT_LIST_LIMIT as f64).round() as i64
+    }
 }
 
 #[derive(Debug, Error)]
@@ -1,10 +1,11 @@
 use std::{
     collections::HashMap,
-    sync::{mpsc::Sender, LazyLock, Mutex, MutexGuard},
+    sync::{LazyLock, Mutex, MutexGuard},
     time::Duration,
 };
 
 use dashmap::DashMap;
+use tokio::sync::mpsc::Sender;
 
 use crate::{logging::LogContext, services::eldenring::PoolError};
 
@@ -38,6 +39,15 @@ impl BreakInPool {
             .collect()
     }
 
+    /// Amount of invadeable hosts per play region.
+    pub fn play_region_counts(&self) -> HashMap<u32, usize> {
+        let mut counts = HashMap::new();
+        for entry in self.entries.iter() {
+            *counts.entry(entry.play_region).or_default() += 1;
+        }
+        counts
+    }
+
     pub fn remove(&self, key: &BreakInPoolKey) -> Result<(), PoolError> {
         self.entries.remove(key).ok_or(PoolError::NotFound)?;
         Ok(())
@@ -151,7 +161,7 @@ impl BreakInAttemptTracker {
 
 #[cfg(test)]
 mod test {
-    use std::sync::mpsc::channel;
+    use tokio::sync::mpsc::channel;
 
     use super::{BreakInPoolEntry, BreakInPoolQuery};
 
@@ -175,7 +185,7 @@ mod test {
 
     #[test]
     fn level_1_characters_match() {
-        let (target_tx, _) = channel();
+        let (target_tx, _) = channel(1);
         let host = BreakInPoolEntry {
             player_id: 1,
             character_level: 1,
@@ -197,7 +207,7 @@ mod test {
 
     #[test]
     fn level_fall_off_applies() {
-        let (target_tx, _) = channel();
+        let (target_tx, _) = channel(1);
         let host = BreakInPoolEntry {
             player_id: 1,
             character_level: 700,
@@ -219,7 +229,7 @@ mod test {
 
     #[test]
     fn play_region_must_match() {
-        let (target_tx, _) = channel();
+        let (target_tx, _) = channel(1);
         let host = BreakInPoolEntry {
             player_id: 1,
             character_level: 1,
@@ -241,7 +251,7 @@ mod test {
 
     #[test]
     fn self_match_fails() {
-        let (target_tx, _) = channel();
+        let (target_tx, _) = channel(1);
         let host = BreakInPoolEntry {
             player_id: 1,
             character_level: 1,
@@ -0,0 +1,115 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How long a computed density is served before it's recomputed from the pools.
+pub const MATCH_DENSITY_TTL: Duration = Duration::from_secs(15);
+
+/// Highest activity bucket reported to the game.
+const MAX_ACTIVITY: u8 = 8;
+
+/// Players available for cooperation (blue) and invasions (red) in a single play region.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RegionActivity {
+    pub blue: usize,
+    pub red: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct MatchDensity {
+    pub regions: BTreeMap<u32, RegionActivity>,
+}
+
+impl MatchDensity {
+    pub fn add_blue(&mut self, counts: HashMap<u32, usize>) {
+        for (play_region, count) in counts {
+            self.regions.entry(play_region).or_default().blue += count;
+        }
+    }
+
+    pub fn add_red(&mut self, counts: HashMap<u32, usize>) {
+        for (play_region, count) in counts {
+            self.regions.entry(play_region).or_default().red += count;
+        }
+    }
+}
+
+/// Maps a player count onto the game's activity buckets. Buckets grow exponentially so a
+/// handful of players already shows up while busy regions don't all end up in the top bucket.
+pub fn activity_bucket(count: usize) -> u8 {
+    (usize::BITS - count.leading_zeros()).min(MAX_ACTIVITY as u32) as u8
+}
+
+/// Holds on to the last computed density for [MATCH_DENSITY_TTL] so clients polling the density
+/// don't have every request walk the pools.
+#[derive(Default)]
+pub struct MatchDensityCache {
+    cached: Mutex<Option<(Instant, Arc<MatchDensity>)>>,
+}
+
+impl MatchDensityCache {
+    /// Returns the cached density or computes a fresh one if it expired. Computation happens
+    /// under the lock so concurrent requests don't all recompute it.
+    pub fn get_or_compute(&self, compute: impl FnOnce() -> MatchDensity) -> Arc<MatchDensity> {
+        let mut cached = self.cached.lock().unwrap();
+        let now = Instant::now();
+
+        match cached.as_ref() {
+            Some((computed_at, density))
+                if now.saturating_duration_since(*computed_at) < MATCH_DENSITY_TTL =>
+            {
+                density.clone()
+            }
+            _ => {
+                let density = Arc::new(compute());
+                *cached = Some((now, density.clone()));
+                density
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{activity_bucket, MatchDensity, MatchDensityCache, RegionActivity};
+
+    #[test]
+    fn maps_counts_to_buckets() {
+        assert_eq!(activity_bucket(0), 0);
+        assert_eq!(activity_bucket(1), 1);
+        assert_eq!(activity_bucket(3), 2);
+        assert_eq!(activity_bucket(4), 3);
+        assert_eq!(activity_bucket(10_000), 8);
+    }
+
+    #[test]
+    fn aggregates_per_play_region() {
+        let mut density = MatchDensity::default();
+        density.add_blue(HashMap::from([(1100000, 2), (1100010, 1)]));
+        density.add_blue(HashMap::from([(1100000, 1)]));
+        density.add_red(HashMap::from([(1100010, 4)]));
+
+        assert_eq!(
+            density.regions[&1100000],
+            RegionActivity { blue: 3, red: 0 }
+        );
+        assert_eq!(
+            density.regions[&1100010],
+            RegionActivity { blue: 1, red: 4 }
+        );
+    }
+
+    #[test]
+    fn serves_cached_density() {
+        let cache = MatchDensityCache::default();
+
+        let first = cache.get_or_compute(MatchDensity::default);
+        let second = cache.get_or_compute(|| panic!("Density should have been cached"));
+
+        assert!(std::sync::Arc::ptr_eq(&first, &second));
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+
+use crate::services::eldenring::PoolError;
+
+/// How long a ticket waits for a match before it expires.
+pub const MATCHING_TICKET_TTL: Duration = Duration::from_secs(300);
+
+/// Where a ticket is at, as reported to the client when polling. The values sent as `unk0` are
+/// made up, they haven't been verified against what the client expects.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchingTicketStatus {
+    /// Still looking for another ticket to match with.
+    Waiting = 0,
+    /// Matched with another player's ticket.
+    Matched = 1,
+    /// Ticket ran out of time or doesn't exist (anymore).
+    Expired = 2,
+}
+
+/// Pool of matching tickets, one per player.
+#[derive(Default)]
+pub struct MatchingTicketPool {
+    entries: DashMap<MatchingTicketKey, MatchingTicketEntry>,
+    /// Held while tickets are paired up or released so two tickets can't claim the same one.
+    pairing: Mutex<()>,
+}
+
+impl MatchingTicketPool {
+    /// Adds the player's ticket and matches it up with a waiting ticket of another player
+    /// with compatible parameters, if there is one.
+    pub fn insert(&self, player_id: i32, entry: MatchingTicketEntry) -> MatchingTicketToken<'_> {
+        let key = MatchingTicketKey(player_id);
+        self.entries.insert(key.clone(), entry);
+        self.try_match(&key);
+
+        MatchingTicketToken(self, key)
+    }
+
+    pub fn get(&self, key: &MatchingTicketKey) -> Option<MatchingTicketEntry> {
+        self.entries.get(key).map(|e| e.clone())
+    }
+
+    /// Status of the ticket. Waiting tickets get another go at finding a match, tickets past
+    /// their lifetime are removed whether they were matched or not.
+    pub fn poll(&self, key: &MatchingTicketKey) -> MatchingTicketStatus {
+        let status = match self.entries.get(key) {
+            Some(entry) if entry.is_expired() => MatchingTicketStatus::Expired,
+            Some(entry) if entry.matched_with.is_some() => MatchingTicketStatus::Matched,
+            Some(_) => MatchingTicketStatus::Waiting,
+            None => MatchingTicketStatus::Expired,
+        };
+
+        match status {
+            MatchingTicketStatus::Expired => {
+                let _ = self.remove(key);
+                status
+            }
+            MatchingTicketStatus::Waiting if self.try_match(key) => MatchingTicketStatus::Matched,
+            _ => status,
+        }
+    }
+
+    /// Removes the ticket. A ticket that was matched with this one goes back to waiting.
+    pub fn remove(&self, key: &MatchingTicketKey) -> Result<(), PoolError> {
+        let _pairing = self.pairing.lock().unwrap();
+        let (_, entry) = self.entries.remove(key).ok_or(PoolError::NotFound)?;
+
+        if let Some(other) = entry.matched_with {
+            if let Some(mut other) = self.entries.get_mut(&MatchingTicketKey(other)) {
+                if other.matched_with == Some(key.0) {
+                    other.matched_with = None;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pairs the ticket up with a compatible waiting ticket of another player. Returns whether
+    /// it found one.
+    fn try_match(&self, key: &MatchingTicketKey) -> bool {
+        let _pairing = self.pairing.lock().unwrap();
+
+        let Some(entry) = self.get(key) else {
+            return false;
+        };
+        if entry.matched_with.is_some() || entry.is_expired() {
+            return false;
+        }
+
+        let Some(candidate) = self
+            .entries
+            .iter()
+            .find(|e| entry.is_compatible(e.value()))
+            .map(|e| e.key().clone())
+        else {
+            return false;
+        };
+
+        // Only one entry is locked at a time, two in the same shard would deadlock.
+        if let Some(mut other) = self.entries.get_mut(&candidate) {
+            other.matched_with = Some(key.0);
+        }
+        if let Some(mut entry) = self.entries.get_mut(key) {
+            entry.matched_with = Some(candidate.0);
+        }
+
+        true
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchingTicketEntry {
+    pub player_id: i32,
+    pub external_id: String,
+    pub unk1: Vec<u32>,
+    pub unk2: u32,
+    pub created_at: Instant,
+    /// Player whose ticket this one got matched with.
+    pub matched_with: Option<i32>,
+}
+
+impl MatchingTicketEntry {
+    fn is_expired(&self) -> bool {
+        self.created_at.elapsed() >= MATCHING_TICKET_TTL
+    }
+
+    /// Whether the other ticket is still waiting and asks for the same kind of match.
+    fn is_compatible(&self, other: &MatchingTicketEntry) -> bool {
+        other.player_id != self.player_id
+            && other.matched_with.is_none()
+            && !other.is_expired()
+            && other.unk2 == self.unk2
+            && other.unk1.iter().any(|v| self.unk1.contains(v))
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MatchingTicketKey(pub i32);
+
+/// Represents an entry in the matching ticket pool. Removes corresponding entry when dropped.
+pub struct MatchingTicketToken<'a>(&'a MatchingTicketPool, pub MatchingTicketKey);
+
+impl Drop for MatchingTicketToken<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.remove(&self.1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::{
+        MatchingTicketEntry, MatchingTicketKey, MatchingTicketPool, MatchingTicketStatus,
+        MATCHING_TICKET_TTL,
+    };
+
+    fn ticket(player_id: i32, unk1: Vec<u32>, unk2: u32) -> MatchingTicketEntry {
+        MatchingTicketEntry {
+            player_id,
+            external_id: player_id.to_string(),
+            unk1,
+            unk2,
+            created_at: Instant::now(),
+            matched_with: None,
+        }
+    }
+
+    #[test]
+    fn matches_compatible_tickets() {
+        let pool = MatchingTicketPool::default();
+
+        let first = pool.insert(1, ticket(1, vec![1, 2], 0));
+        let _other_kind = pool.insert(3, ticket(3, vec![2], 1));
+        assert_eq!(pool.poll(&first.1), MatchingTicketStatus::Waiting);
+
+        let second = pool.insert(2, ticket(2, vec![2, 3], 0));
+        assert_eq!(pool.poll(&first.1), MatchingTicketStatus::Matched);
+        assert_eq!(pool.poll(&second.1), MatchingTicketStatus::Matched);
+        assert_eq!(pool.get(&first.1).unwrap().matched_with, Some(2));
+        assert_eq!(
+            pool.poll(&MatchingTicketKey(3)),
+            MatchingTicketStatus::Waiting
+        );
+    }
+
+    #[test]
+    fn dropping_ticket_releases_match() {
+        let pool = MatchingTicketPool::default();
+
+        let first = pool.insert(1, ticket(1, vec![1], 0));
+        let second = pool.insert(2, ticket(2, vec![1], 0));
+        drop(second);
+
+        assert_eq!(pool.poll(&first.1), MatchingTicketStatus::Waiting);
+        assert_eq!(
+            pool.poll(&MatchingTicketKey(2)),
+            MatchingTicketStatus::Expired
+        );
+    }
+
+    #[test]
+    fn expires_old_tickets() {
+        let pool = MatchingTicketPool::default();
+        let mut entry = ticket(1, vec![1], 0);
+        entry.created_at = Instant::now() - MATCHING_TICKET_TTL;
+
+        let token = pool.insert(1, entry);
+        let _second = pool.insert(2, ticket(2, vec![1], 0));
+
+        assert_eq!(pool.poll(&token.1), MatchingTicketStatus::Expired);
+        assert!(pool.get(&token.1).is_none());
+    }
+
+    #[test]
+    fn rematches_released_ticket_when_polled() {
+        let pool = MatchingTicketPool::default();
+
+        let first = pool.insert(1, ticket(1, vec![1], 0));
+        let second = pool.insert(2, ticket(2, vec![1], 0));
+        let third = pool.insert(3, ticket(3, vec![1], 0));
+        assert_eq!(pool.poll(&third.1), MatchingTicketStatus::Waiting);
+
+        drop(second);
+        assert_eq!(pool.poll(&first.1), MatchingTicketStatus::Matched);
+        assert_eq!(pool.get(&first.1).unwrap().matched_with, Some(3));
+        assert_eq!(pool.poll(&third.1), MatchingTicketStatus::Matched);
+    }
+
+    #[test]
+    fn expires_matched_tickets() {
+        let pool = MatchingTicketPool::default();
+
+        let first = pool.insert(1, ticket(1, vec![1], 0));
+        let second = pool.insert(2, ticket(2, vec![1], 0));
+        pool.entries.get_mut(&first.1).unwrap().created_at = Instant::now() - MATCHING_TICKET_TTL;
+
+        assert_eq!(pool.poll(&first.1), MatchingTicketStatus::Expired);
+        assert!(pool.get(&first.1).is_none());
+        assert_eq!(pool.poll(&second.1), MatchingTicketStatus::Waiting);
+    }
+}
@@ -0,0 +1,172 @@
+use std::{fs::File, path::Path};
+
+use message::eldenring::BloodMessageData;
+use serde::Deserialize;
+
+/// Conjunction value for messages consisting of only a single sentence.
+const NO_CONJUNCTION: i8 = -1;
+
+/// What to do with a blood message matching one of the filter rules.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// Pretend the message was stored without actually storing it.
+    Drop,
+    /// Store the message but only show it to its author.
+    #[default]
+    Shadow,
+    /// Tell the client that creating the message failed.
+    Reject,
+}
+
+impl FilterAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterAction::Drop => "drop",
+            FilterAction::Shadow => "shadow",
+            FilterAction::Reject => "reject",
+        }
+    }
+}
+
+/// Blocklist of template and word combinations. Read from disk on every use so it can be edited
+/// without restarting the server.
+#[derive(Debug, Default, Deserialize)]
+pub struct BloodMessageFilter {
+    /// Action applied by rules that don't specify one themselves.
+    #[serde(default)]
+    pub action: FilterAction,
+    /// Action applied to messages whose data can't be decoded, as those can't be checked against
+    /// the rules.
+    #[serde(default)]
+    pub undecodable: FilterAction,
+    #[serde(default)]
+    pub rules: Vec<BloodMessageFilterRule>,
+}
+
+/// Matches a single sentence of a message. Leaving out the template or word matches any.
+#[derive(Debug, Deserialize)]
+pub struct BloodMessageFilterRule {
+    pub template: Option<u16>,
+    pub word: Option<i32>,
+    pub action: Option<FilterAction>,
+}
+
+impl BloodMessageFilterRule {
+    fn matches(&self, template: u16, word: i32) -> bool {
+        self.template.is_none_or(|t| t == template) && self.word.is_none_or(|w| w == word)
+    }
+}
+
+impl BloodMessageFilter {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(serde_yaml::from_reader(File::open(path)?)?)
+    }
+
+    /// Returns the action of the first rule matching either sentence of the message.
+    pub fn check(&self, message: &BloodMessageData) -> Option<FilterAction> {
+        let mut sentences = vec![(message.template1, message.word1)];
+        if message.conjunction != NO_CONJUNCTION {
+            sentences.push((message.template2, message.word2));
+        }
+
+        self.rules
+            .iter()
+            .find(|rule| sentences.iter().any(|(t, w)| rule.matches(*t, *w)))
+            .map(|rule| rule.action.unwrap_or(self.action))
+    }
+
+    /// Like [Self::check] but takes the message data as sent by the client.
+    pub fn check_data(&self, data: &[u8]) -> Option<FilterAction> {
+        match wire::deserialize::<BloodMessageData>(data) {
+            Ok(message) => self.check(&message),
+            Err(_) => Some(self.undecodable),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use message::eldenring::BloodMessageData;
+    use wire::deserialize;
+
+    use super::{BloodMessageFilter, FilterAction};
+
+    fn message() -> BloodMessageData {
+        deserialize(include_bytes!(
+            "../../../../message/test/data/BloodMessageData.bin"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_template_and_word() {
+        let filter: BloodMessageFilter = serde_yaml::from_str(
+            "
+            action: reject
+            rules:
+              - template: 10000
+                word: 34004
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(filter.check(&message()), Some(FilterAction::Reject));
+    }
+
+    #[test]
+    fn rule_action_overrides_default() {
+        let filter: BloodMessageFilter = serde_yaml::from_str(
+            "
+            rules:
+              - word: 1
+              - word: 34004
+                action: drop
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(filter.check(&message()), Some(FilterAction::Drop));
+    }
+
+    #[test]
+    fn ignores_second_sentence_without_conjunction() {
+        let filter: BloodMessageFilter = serde_yaml::from_str(
+            "
+            rules:
+              - template: 0
+                word: 0
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(filter.check(&message()), None);
+
+        let mut message = message();
+        message.conjunction = 0;
+        assert_eq!(filter.check(&message), Some(FilterAction::Shadow));
+    }
+
+    #[test]
+    fn applies_action_to_undecodable_data() {
+        let filter: BloodMessageFilter = serde_yaml::from_str(
+            "
+            undecodable: reject
+            rules:
+              - word: 1
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(filter.check_data(&[0x01]), Some(FilterAction::Reject));
+        assert_eq!(
+            filter.check_data(include_bytes!(
+                "../../../../message/test/data/BloodMessageData.bin"
+            )),
+            None
+        );
+
+        let filter = BloodMessageFilter::default();
+        assert_eq!(filter.check_data(&[0x01]), Some(FilterAction::Shadow));
+    }
+}
@@ -0,0 +1,56 @@
+use clap::ValueEnum;
+
+/// How blood messages are picked for `GetBloodMessageList`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SelectionStrategy {
+    /// Every message in the requested play regions is equally likely to show up.
+    #[default]
+    Uniform,
+    /// Messages are drawn from a larger random sample with a bias towards well rated messages
+    /// by well rated authors and recent messages.
+    Weighted,
+}
+
+/// Exponents and decay rates making up a message's weight for [SelectionStrategy::Weighted].
+/// A weight of zero disables the respective factor.
+#[derive(Clone, Copy, Debug)]
+pub struct SelectionWeights {
+    /// Exponent applied to the share of positive ratings of the message.
+    pub rating: f64,
+    /// Decay of the weight per day since the message was written.
+    pub age: f64,
+    /// Exponent applied to the share of positive ratings the author's messages received over
+    /// time.
+    pub reputation: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BloodMessageSelection {
+    pub strategy: SelectionStrategy,
+    pub weights: SelectionWeights,
+}
+
+/// Parses a weight for [SelectionWeights]. Negative weights would favor badly rated and old
+/// messages, so only finite weights of zero or more are accepted.
+pub fn parse_weight(value: &str) -> Result<f64, String> {
+    let weight = value.parse::<f64>().map_err(|e| e.to_string())?;
+    if !weight.is_finite() || weight < 0.0 {
+        return Err(format!("{value} is not a finite number of zero or more"));
+    }
+
+    Ok(weight)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_weight;
+
+    #[test]
+    fn rejects_negative_weights() {
+        assert_eq!(parse_weight("0.5"), Ok(0.5));
+        assert_eq!(parse_weight("0"), Ok(0.0));
+        assert!(parse_weight("-0.1").is_err());
+        assert!(parse_weight("inf").is_err());
+        assert!(parse_weight("NaN").is_err());
+    }
+}
@@ -1,6 +1,7 @@
-use std::sync::{mpsc::Sender, LazyLock};
+use std::sync::LazyLock;
 
 use dashmap::DashMap;
+use tokio::sync::mpsc::Sender;
 
 use crate::services::eldenring::PoolError;
 
@@ -151,7 +152,7 @@ impl QuickMatchJoinAttemptTracker {
 
 #[cfg(test)]
 mod test {
-    use std::sync::mpsc::channel;
+    use tokio::sync::mpsc::channel;
 
     use super::{QuickMatchPoolEntry, QuickMatchPoolQuery};
 
@@ -173,7 +174,7 @@ mod test {
 
     #[test]
     fn level_1_characters_match() {
-        let (host_tx, _) = channel();
+        let (host_tx, _) = channel(1);
         let host = QuickMatchPoolEntry {
             host_player_id: 1,
             host_external_id: String::new(),
@@ -199,7 +200,7 @@ mod test {
 
     #[test]
     fn doesnt_match_differing_levels() {
-        let (host_tx, _) = channel();
+        let (host_tx, _) = channel(1);
         let host = QuickMatchPoolEntry {
             host_player_id: 1,
             host_external_id: String::new(),
@@ -225,7 +226,7 @@ mod test {
 
     #[test]
     fn password_matches_regardless() {
-        let (host_tx, _) = channel();
+        let (host_tx, _) = channel(1);
         let host = QuickMatchPoolEntry {
             host_player_id: 1,
             host_external_id: String::new(),
@@ -251,7 +252,7 @@ mod test {
 
     #[test]
     fn doesnt_match_on_differing_passwords() {
-        let (host_tx, _) = channel();
+        let (host_tx, _) = channel(1);
         let host = QuickMatchPoolEntry {
             host_player_id: 1,
             host_external_id: String::new(),
@@ -277,7 +278,7 @@ mod test {
 
     #[test]
     fn doesnt_match_when_password_isnt_set_on_joiner() {
-        let (host_tx, _) = channel();
+        let (host_tx, _) = channel(1);
         let host = QuickMatchPoolEntry {
             host_player_id: 1,
             host_external_id: String::new(),
@@ -303,7 +304,7 @@ mod test {
 
     #[test]
     fn doesnt_match_mismatching_arena_ids() {
-        let (host_tx, _) = channel();
+        let (host_tx, _) = channel(1);
         let host = QuickMatchPoolEntry {
             host_player_id: 1,
             host_external_id: String::new(),
@@ -329,7 +330,7 @@ mod test {
 
     #[test]
     fn doesnt_match_mismatching_settings() {
-        let (host_tx, _) = channel();
+        let (host_tx, _) = channel(1);
         let host = QuickMatchPoolEntry {
             host_player_id: 1,
             host_external_id: String::new(),
@@ -355,7 +356,7 @@ mod test {
 
     #[test]
     fn test_clayamore_group() {
-        let (host_tx, _) = channel();
+        let (host_tx, _) = channel(1);
         let host = QuickMatchPoolEntry {
             host_player_id: 1,
             host_external_id: String::new(),
@@ -381,7 +382,7 @@ mod test {
 
     #[test]
     fn test_self_match_fails() {
-        let (host_tx, _) = channel();
+        let (host_tx, _) = channel(1);
         let host = QuickMatchPoolEntry {
             host_player_id: 1,
             host_external_id: String::new(),
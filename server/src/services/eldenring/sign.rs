@@ -1,7 +1,7 @@
 use std::{
+    collections::HashMap,
     sync::{
         atomic::{AtomicI64, Ordering},
-        mpsc::Sender,
         LazyLock,
     },
     time::Duration,
@@ -9,6 +9,7 @@ use std::{
 
 use dashmap::DashMap;
 use message::eldenring::{PlayRegionArea, PuddleArea};
+use tokio::sync::mpsc::Sender;
 
 use crate::services::eldenring::{area::MatchingArea, PoolError};
 
@@ -54,6 +55,18 @@ impl SignPool {
             .collect()
     }
 
+    /// Amount of signs per play region. Puddle signs aren't placed in a play region and are left
+    /// out.
+    pub fn play_region_counts(&self) -> HashMap<u32, usize> {
+        let mut counts = HashMap::new();
+        for entry in self.entries.iter() {
+            if let MatchingArea::PlayRegion(area) = &entry.location {
+                *counts.entry(area.play_region).or_default() += 1;
+            }
+        }
+        counts
+    }
+
     pub fn remove(&self, key: &SignPoolKey) -> Result<(), PoolError> {
         self.entries.remove(key).ok_or(PoolError::NotFound)?;
         Ok(())
@@ -206,7 +219,7 @@ impl SummonAttemptTracker {
 
 #[cfg(test)]
 mod test {
-    use std::sync::mpsc::channel;
+    use tokio::sync::mpsc::channel;
 
     use message::eldenring::PlayRegionArea;
 
@@ -232,7 +245,7 @@ mod test {
 
     #[test]
     fn level_1_characters_match() {
-        let (summonee_tx, _) = channel();
+        let (summonee_tx, _) = channel(1);
         let host = SignPoolEntry {
             external_id: String::new(),
             player_id: 1,
@@ -264,7 +277,7 @@ mod test {
 
     #[test]
     fn doesnt_match_differing_levels() {
-        let (summonee_tx, _) = channel();
+        let (summonee_tx, _) = channel(1);
         let host = SignPoolEntry {
             player_id: 1,
             external_id: String::new(),
@@ -296,7 +309,7 @@ mod test {
 
     #[test]
     fn password_matches_regardless() {
-        let (summonee_tx, _) = channel();
+        let (summonee_tx, _) = channel(1);
         let host = SignPoolEntry {
             player_id: 1,
             external_id: String::new(),
@@ -328,7 +341,7 @@ mod test {
 
     #[test]
     fn doesnt_match_on_differing_passwords() {
-        let (summonee_tx, _) = channel();
+        let (summonee_tx, _) = channel(1);
         let host = SignPoolEntry {
             player_id: 1,
             external_id: String::new(),
@@ -360,7 +373,7 @@ mod test {
 
     #[test]
     fn doesnt_match_when_password_isnt_set_on_host() {
-        let (summonee_tx, _) = channel();
+        let (summonee_tx, _) = channel(1);
         let host = SignPoolEntry {
             player_id: 1,
             external_id: String::new(),
@@ -392,7 +405,7 @@ mod test {
 
     #[test]
     fn doesnt_match_across_search_areas() {
-        let (summonee_tx, _) = channel();
+        let (summonee_tx, _) = channel(1);
         let host = SignPoolEntry {
             player_id: 1,
             external_id: String::new(),
@@ -424,7 +437,7 @@ mod test {
 
     #[test]
     fn self_match_fails() {
-        let (summonee_tx, _) = channel();
+        let (summonee_tx, _) = channel(1);
         let host = SignPoolEntry {
             player_id: 1,
             external_id: String::new(),
@@ -1,10 +1,11 @@
 use std::{
     collections::HashMap,
-    sync::{mpsc::Sender, LazyLock, Mutex, MutexGuard},
+    sync::{LazyLock, Mutex, MutexGuard},
 };
 
 use dashmap::DashMap;
 use message::eldenring::VisitType;
+use tokio::sync::mpsc::Sender;
 
 use crate::{logging::LogContext, services::eldenring::PoolError};
 
@@ -142,7 +143,7 @@ impl VisitorAttemptTracker {
 
 #[cfg(test)]
 mod test {
-    use std::sync::mpsc::channel;
+    use tokio::sync::mpsc::channel;
 
     use message::eldenring::VisitType;
 
@@ -168,7 +169,7 @@ mod test {
 
     #[test]
     fn level_1_characters_match() {
-        let (visitor_tx, _) = channel();
+        let (visitor_tx, _) = channel(1);
         let visitor = VisitorPoolEntry {
             player_id: 1,
             character_level: 1,
@@ -192,7 +193,7 @@ mod test {
 
     #[test]
     fn level_fall_off_applies() {
-        let (visitor_tx, _) = channel();
+        let (visitor_tx, _) = channel(1);
         let visitor = VisitorPoolEntry {
             player_id: 1,
             character_level: 700,
@@ -216,7 +217,7 @@ mod test {
 
     #[test]
     fn play_region_must_match() {
-        let (visitor_tx, _) = channel();
+        let (visitor_tx, _) = channel(1);
         let visitor = VisitorPoolEntry {
             player_id: 1,
             character_level: 1,
@@ -240,7 +241,7 @@ mod test {
 
     #[test]
     fn self_match_fails() {
-        let (visitor_tx, _) = channel();
+        let (visitor_tx, _) = channel(1);
         let visitor = VisitorPoolEntry {
             player_id: 1,
             character_level: 1,
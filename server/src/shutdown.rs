@@ -0,0 +1,193 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
+
+use actix_web::dev::ServerHandle;
+use tokio::{
+    sync::{watch, Notify},
+    time::Instant,
+};
+
+use crate::{
+    logging::LogContext, notification::announcement_push, services::eldenring::GameServices,
+};
+
+/// How long connections get to wind down after they've been told to close.
+pub const SHUTDOWN_CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum ShutdownPhase {
+    /// Business as usual.
+    Running,
+    /// No longer accepting new connections, waiting for clients to leave.
+    Draining,
+    /// Grace period is over, all remaining connections should close.
+    Terminating,
+}
+
+/// Orchestrates stopping the server without pulling the rug from under connected clients.
+pub struct ShutdownCoordinator {
+    services: Arc<GameServices>,
+    grace_period: Duration,
+    message: String,
+    phase: watch::Sender<ShutdownPhase>,
+    connections: AtomicUsize,
+    disconnected: Notify,
+    api_handle: OnceLock<ServerHandle>,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(services: Arc<GameServices>, grace_period: Duration, message: String) -> Self {
+        Self {
+            services,
+            grace_period,
+            message,
+            phase: watch::Sender::new(ShutdownPhase::Running),
+            connections: AtomicUsize::default(),
+            disconnected: Notify::new(),
+            api_handle: OnceLock::new(),
+        }
+    }
+
+    /// Registers the API server so it can be stopped once everything else has wound down.
+    pub fn set_api_handle(&self, handle: ServerHandle) {
+        let _ = self.api_handle.set(handle);
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<ShutdownPhase> {
+        self.phase.subscribe()
+    }
+
+    pub fn connection_count(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Tracks a client connection for the duration of the returned guard.
+    pub fn track_connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(self.clone())
+    }
+
+    /// Starts the shutdown sequence in the background. Uses the configured grace period and
+    /// message if none are supplied. Returns false if a shutdown is already underway.
+    pub fn initiate(
+        self: &Arc<Self>,
+        grace_period: Option<Duration>,
+        message: Option<String>,
+    ) -> bool {
+        let started = self.phase.send_if_modified(|phase| {
+            if *phase == ShutdownPhase::Running {
+                *phase = ShutdownPhase::Draining;
+                true
+            } else {
+                false
+            }
+        });
+
+        if started {
+            let coordinator = self.clone();
+            let grace_period = grace_period.unwrap_or(self.grace_period);
+            let message = message.unwrap_or_else(|| self.message.clone());
+            tokio::spawn(LogContext::with(async move {
+                coordinator.run(grace_period, &message).await
+            }));
+        }
+
+        started
+    }
+
+    /// Initiates the shutdown sequence once the process is asked to stop.
+    pub async fn listen_for_signals(self: Arc<Self>) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut sigterm =
+                signal(SignalKind::terminate()).expect("Could not listen for SIGTERM");
+            tokio::select! {
+                _ = sigterm.recv() => {},
+                _ = tokio::signal::ctrl_c() => {},
+            }
+        }
+
+        #[cfg(not(unix))]
+        let _ = tokio::signal::ctrl_c().await;
+
+        log::info!("Received shutdown signal");
+        self.initiate(None, None);
+    }
+
+    async fn run(&self, grace_period: Duration, message: &str) {
+        let minutes = grace_period.as_secs().div_ceil(60);
+        log::info!(
+            grace_period = grace_period.as_secs(),
+            connections = self.connection_count();
+            "Draining clients before shutdown"
+        );
+
+        match announcement_push(&message.replace("{minutes}", &minutes.to_string())) {
+            Ok(push) => {
+                let _ = self.services.notifications.broadcast(push);
+            }
+            Err(e) => log::error!(
+                error:? = e;
+                "Could not build shutdown announcement."
+            ),
+        }
+
+        self.wait_for_disconnects(Instant::now() + grace_period)
+            .await;
+
+        log::info!(
+            connections = self.connection_count();
+            "Closing remaining connections"
+        );
+        self.phase.send_replace(ShutdownPhase::Terminating);
+        self.wait_for_disconnects(Instant::now() + SHUTDOWN_CLOSE_TIMEOUT)
+            .await;
+
+        log::info!("Stopping API server");
+        if let Some(handle) = self.api_handle.get() {
+            handle.stop(true).await;
+        }
+    }
+
+    /// Waits until all tracked connections have closed or the deadline passes.
+    async fn wait_for_disconnects(&self, deadline: Instant) {
+        loop {
+            let disconnected = self.disconnected.notified();
+            if self.connection_count() == 0 {
+                return;
+            }
+
+            tokio::select! {
+                _ = disconnected => {},
+                _ = tokio::time::sleep_until(deadline) => return,
+            }
+        }
+    }
+}
+
+/// Resolves once the shutdown sequence has progressed to at least the given phase.
+pub async fn reached(phase: &mut watch::Receiver<ShutdownPhase>, target: ShutdownPhase) {
+    while *phase.borrow_and_update() < target {
+        if phase.changed().await.is_err() {
+            // Coordinator is gone so the phase can't advance anymore.
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Represents a connected client. Lets the coordinator know the client left when dropped.
+pub struct ConnectionGuard(Arc<ShutdownCoordinator>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::Relaxed);
+        self.0.disconnected.notify_waiters();
+    }
+}
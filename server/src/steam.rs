@@ -1,15 +1,222 @@
 use std::{
-    sync::mpsc::{channel, Sender},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
-use steamworks::{Server, SteamAPIInitError, SteamId};
+use dashmap::{mapref::entry::Entry, DashMap};
+use steamworks::{
+    AuthSessionError, Server, SteamAPIInitError, SteamId, ValidateAuthTicketResponse,
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+use crate::{
+    identity::{
+        IdentityClaims, IdentityError, IdentityProvider, IdentitySession, IdentityValidation,
+    },
+    logging::LogContext,
+};
+
+/// Whatever auth sessions are begun and ended with. Only holds a single auth session per steam ID.
+pub trait AuthSessionBackend: Send + Sync + 'static {
+    fn begin_authentication_session(
+        &self,
+        steam_id: SteamId,
+        ticket: &[u8],
+    ) -> Result<(), AuthSessionError>;
+
+    fn end_authentication_session(&self, steam_id: SteamId);
+}
+
+impl AuthSessionBackend for Server {
+    fn begin_authentication_session(
+        &self,
+        steam_id: SteamId,
+        ticket: &[u8],
+    ) -> Result<(), AuthSessionError> {
+        Server::begin_authentication_session(self, steam_id, ticket)
+    }
+
+    fn end_authentication_session(&self, steam_id: SteamId) {
+        Server::end_authentication_session(self, steam_id)
+    }
+}
+
+/// Ticket a connection began an auth session with, along with the channel its validation outcomes
+/// go to. The nonce tells connections of the same steam ID apart.
+struct AuthTicket {
+    nonce: u64,
+    ticket: Vec<u8>,
+    validation_tx: UnboundedSender<Result<(), IdentityError>>,
+}
+
+/// Auth session held for a steam ID. A new ticket only takes over for good once it has been
+/// validated, until then the ticket it replaced is kept around so a forged ticket can't knock the
+/// player that is actually online off of Steam validation.
+struct AuthSession {
+    active: AuthTicket,
+    superseded: Option<AuthTicket>,
+}
+
+/// Keeps track of which connection the auth session of each steam ID belongs to and routes the
+/// ValidateAuthTicket callbacks to it.
+pub struct AuthSessions<B> {
+    backend: B,
+    sessions: DashMap<u64, AuthSession>,
+    counter: AtomicU64,
+}
+
+impl<B: AuthSessionBackend> AuthSessions<B> {
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            sessions: DashMap::default(),
+            counter: AtomicU64::default(),
+        }
+    }
+
+    /// Begins an auth session for the ticket. The entry of the steam ID stays locked throughout,
+    /// so the callback can't be missed and no other session for the steam ID can be begun or ended
+    /// in between.
+    pub fn start(
+        self: &Arc<Self>,
+        steam_id: SteamId,
+        ticket: &[u8],
+    ) -> Result<SteamSession<B>, AuthSessionError> {
+        let nonce = self.counter.fetch_add(1, Ordering::Relaxed);
+        let (validation_tx, validation_rx) = unbounded_channel();
+        let ticket = AuthTicket {
+            nonce,
+            ticket: ticket.to_vec(),
+            validation_tx,
+        };
+
+        match self.sessions.entry(steam_id.raw()) {
+            Entry::Vacant(vacant) => {
+                self.backend
+                    .begin_authentication_session(steam_id, &ticket.ticket)?;
+                vacant.insert(AuthSession {
+                    active: ticket,
+                    superseded: None,
+                });
+            }
+            Entry::Occupied(mut occupied) => {
+                // The current auth session has to go for the new ticket to be checked at all.
+                self.backend.end_authentication_session(steam_id);
+                if let Err(e) = self
+                    .backend
+                    .begin_authentication_session(steam_id, &ticket.ticket)
+                {
+                    self.resume(steam_id, &occupied.get().active);
+                    return Err(e);
+                }
+
+                let session = occupied.get_mut();
+                let previous = std::mem::replace(&mut session.active, ticket);
+                if session.superseded.is_none() {
+                    session.superseded = Some(previous);
+                } else {
+                    // The replaced ticket wasn't validated yet either, its connection is told it
+                    // lost out rather than being left to take the closed channel for a pass.
+                    let _ = previous
+                        .validation_tx
+                        .send(Err(AuthSessionError::DuplicateRequest.into()));
+                }
+            }
+        }
+
+        Ok(SteamSession {
+            steam_id,
+            nonce,
+            sessions: self.clone(),
+            validation_rx: Some(validation_rx),
+        })
+    }
+
+    /// Hands the outcome of a ValidateAuthTicket callback to the connection holding the auth
+    /// session of the steam ID.
+    pub fn report(&self, steam_id: u64, outcome: Result<(), IdentityError>) {
+        match self.sessions.get_mut(&steam_id) {
+            Some(mut session) => {
+                if outcome.is_ok() {
+                    session.superseded = None;
+                }
+                let _ = session.active.validation_tx.send(outcome);
+            }
+            None => log::warn!(
+                steam_id = steam_id;
+                "Received ValidateAuthTicket callback for unknown session."
+            ),
+        }
+    }
 
-use crate::logging::LogContext;
+    fn release(&self, steam_id: SteamId, nonce: u64) {
+        let Entry::Occupied(mut occupied) = self.sessions.entry(steam_id.raw()) else {
+            return;
+        };
+
+        let session = occupied.get_mut();
+        if session.active.nonce == nonce {
+            log::info!(
+                context:serde = LogContext::current(),
+                steam_id = steam_id.raw();
+                "Ending steam session."
+            );
+            self.backend.end_authentication_session(steam_id);
+
+            // The ticket never got validated, the auth session goes back to the connection it was
+            // taken from.
+            match session.superseded.take() {
+                Some(superseded) => {
+                    self.resume(steam_id, &superseded);
+                    session.active = superseded;
+                }
+                None => {
+                    occupied.remove();
+                }
+            }
+        } else if session
+            .superseded
+            .as_ref()
+            .is_some_and(|superseded| superseded.nonce == nonce)
+        {
+            session.superseded = None;
+        }
+        // Otherwise a newer session took over the auth session, ending it would stop Steam from
+        // reporting on the newer one.
+    }
+
+    fn resume(&self, steam_id: SteamId, ticket: &AuthTicket) {
+        if let Err(e) = self
+            .backend
+            .begin_authentication_session(steam_id, &ticket.ticket)
+        {
+            log::error!(
+                context:serde = LogContext::current(),
+                steam_id = steam_id.raw(),
+                error:? = e;
+                "Could not resume steam session."
+            );
+        }
+    }
+}
+
+impl<B: AuthSessionBackend> IdentityProvider for Arc<AuthSessions<B>> {
+    fn start_session(
+        &self,
+        claims: &IdentityClaims,
+    ) -> Result<Box<dyn IdentitySession>, IdentityError> {
+        Ok(Box::new(self.start(
+            SteamId::from_raw(claims.external_id),
+            &claims.session_ticket,
+        )?))
+    }
+}
 
 pub struct SteamServer {
-    server: Server,
-    session_end_tx: Sender<SteamId>,
+    sessions: Arc<AuthSessions<Server>>,
 }
 
 impl SteamServer {
@@ -22,71 +229,57 @@ impl SteamServer {
             "",
         )?;
 
-        let (session_end_tx, session_end_rx) = channel();
+        let sessions = Arc::new(AuthSessions::new(server.clone()));
         {
-            let server = server.clone();
+            let sessions = sessions.clone();
             tokio::spawn(async move {
                 log::info!("Starting steam poll loop");
 
-                loop {
-                    // End any queued session for ending
-                    while let Ok(steam_id) = session_end_rx.try_recv() {
-                        server.end_authentication_session(steam_id);
-                    }
+                // Steam will call this once the ticket has been validated, as well as any time
+                // after when the validity changes (VAC ban, license revoked, logged in elsewhere).
+                let _validate_callback =
+                    server.register_callback(move |v: ValidateAuthTicketResponse| {
+                        sessions.report(
+                            v.steam_id.raw(),
+                            v.response.map_err(IdentityError::SteamValidation),
+                        );
+                    });
 
+                loop {
                     single.run_callbacks();
                     tokio::time::sleep(Duration::from_millis(100)).await;
                 }
             });
         }
 
-        Ok(Self {
-            server,
-            session_end_tx,
-        })
+        Ok(Self { sessions })
     }
+}
 
-    pub fn start_session(
+impl IdentityProvider for SteamServer {
+    fn start_session(
         &self,
-        steam_id: u64,
-        ticket: &[u8],
-    ) -> Result<SteamSession, steamworks::AuthSessionError> {
-        let steam_id = SteamId::from_raw(steam_id);
-        match self.server.begin_authentication_session(steam_id, ticket) {
-            Err(steamworks::AuthSessionError::DuplicateRequest) => {
-                self.server.end_authentication_session(steam_id);
-                self.server.begin_authentication_session(steam_id, ticket)?;
-            }
-            Err(e) => return Err(e),
-            _ => {}
-        }
-
-        // TODO: listen for ValidateAuthTicket callbacks
-        Ok(SteamSession {
-            steam_id,
-            session_end_tx: self.session_end_tx.clone(),
-        })
+        claims: &IdentityClaims,
+    ) -> Result<Box<dyn IdentitySession>, IdentityError> {
+        self.sessions.start_session(claims)
     }
 }
 
-pub struct SteamSession {
+pub struct SteamSession<B: AuthSessionBackend> {
     steam_id: SteamId,
-    session_end_tx: Sender<SteamId>,
+    nonce: u64,
+    sessions: Arc<AuthSessions<B>>,
+    validation_rx: Option<IdentityValidation>,
+}
+
+impl<B: AuthSessionBackend> IdentitySession for SteamSession<B> {
+    fn validation(&mut self) -> Option<IdentityValidation> {
+        self.validation_rx.take()
+    }
 }
 
-impl Drop for SteamSession {
+impl<B: AuthSessionBackend> Drop for SteamSession<B> {
     fn drop(&mut self) {
-        log::info!(
-            context:serde = LogContext::current(),
-            steam_id = self.steam_id.raw();
-            "Request steam session end."
-        );
-        if let Err(e) = self.session_end_tx.send(self.steam_id) {
-            log::error!(
-                context:serde = LogContext::current(),
-                error:? = e;
-                "Could not send session end request to steam server."
-            );
-        }
+        self.sessions.release(self.steam_id, self.nonce);
     }
 }